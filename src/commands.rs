@@ -0,0 +1,132 @@
+use fp::logic::database::Database;
+use fp::logic::history::{HistoryFilter, format_date, parse_date};
+use fp::logic::protection::new_protection;
+
+use crate::args::Args;
+
+/// Time the healthcheck waits for the status API before failing.
+const HEALTHCHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Run a management command against the database of the service.
+pub async fn run(args: &Args) -> Result<(), String> {
+    // the healthcheck asks the running service, it never opens the database
+    if args.command == ["healthcheck"] {
+        return healthcheck(args).await;
+    }
+
+    let Some(database_path) = &args.database_path else {
+        return Err("Commands require the database path (-d or FP_DATABASE_PATH)".to_string());
+    };
+    let mut database = Database::new(Some(database_path.clone()));
+    database
+        .connect()
+        .await
+        .map_err(|_| "Failed to connect to database".to_string())?;
+
+    let command: Vec<&str> = args.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["protection", "add", kind, value] => {
+            let protection = new_protection(kind, value, current_time())?;
+            let id = database.add_protection(&protection).await;
+            println!(
+                "Added protection {} ({} {})",
+                id, protection.kind, protection.value
+            );
+        }
+        ["protection", "list"] => {
+            println!("ID\tKIND\tVALUE\tCREATED");
+            for protection in database.list_protections().await {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    protection.id, protection.kind, protection.value, protection.created_date
+                );
+            }
+        }
+        ["protection", "remove", id] => {
+            let id = id
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid protection id '{}'", id))?;
+            if !database.remove_protection(id).await {
+                return Err(format!("Protection {} does not exist", id));
+            }
+            println!("Removed protection {}", id);
+        }
+        ["history"] => {
+            let filter = HistoryFilter {
+                from: args
+                    .history_from
+                    .as_deref()
+                    .map(|date| parse_date(date, false))
+                    .transpose()?,
+                to: args
+                    .history_to
+                    .as_deref()
+                    .map(|date| parse_date(date, true))
+                    .transpose()?,
+                name: args.history_name.clone(),
+            };
+            println!("DATE\tINSTANCE\tNAME\tHASH\tSIZE\tREASON\tRULE\tMODE\tRESULT");
+            for entry in database.list_history(&filter).await {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    format_date(entry.deleted_date),
+                    entry.instance,
+                    entry.name,
+                    entry.hash,
+                    entry.size,
+                    entry.reason,
+                    entry.rule.as_deref().unwrap_or("-"),
+                    entry.mode,
+                    entry.result
+                );
+            }
+        }
+        _ => {
+            return Err(format!(
+                "Invalid command '{}'. Please check help with -h.",
+                args.command.join(" ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the readiness of the service running with the same configuration through its status
+/// API, the last scans must have succeeded recently.
+async fn healthcheck(args: &Args) -> Result<(), String> {
+    let Some(address) = &args.status_address else {
+        return Err(
+            "The healthcheck requires the status API address (--status-address or FP_STATUS_ADDRESS)"
+                .to_string(),
+        );
+    };
+    // the service may listen on every interface, the healthcheck runs on the same host
+    let address = address
+        .replace("0.0.0.0", "127.0.0.1")
+        .replace("[::]", "[::1]");
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/health", address))
+        .timeout(std::time::Duration::from_secs(HEALTHCHECK_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| format!("Service is unreachable: {}", e))?;
+    let healthy = response.status().is_success();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the health of the service: {}", e))?;
+    if !healthy {
+        return Err(format!("Service is unhealthy: {}", body));
+    }
+    println!("Service is healthy: {}", body);
+    Ok(())
+}
+
+fn current_time() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
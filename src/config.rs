@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+
+use fp::logic::email::{DigestPeriod, SmtpTls};
+use fp::logic::notify::WebhookPreset;
+use fp::logic::plan::FreeSpaceOrder;
+use fp::logic::rules::Rule;
+use serde::Deserialize;
+
+use crate::args::{Args, InstanceArgs};
+
+/// Settings of the TOML file given with `--config`. The keys are the names of the environment
+/// variables in lowercase without the `FP_` prefix, the `FP_WEBHOOK_*`, `FP_SMTP_*` and
+/// `FP_EMAIL_*` ones are grouped in the `[webhook]`, `[smtp]` and `[email]` tables.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    client: Option<String>,
+    monitoring_url: Option<String>,
+    database_path: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    scan_interval: Option<u32>,
+    file_lifetime: Option<u32>,
+    file_lifetime_after_copied: Option<u32>,
+    dry_run: Option<bool>,
+    keep_data: Option<bool>,
+    stop_lifetime: Option<u32>,
+    trash_dir: Option<String>,
+    trash_grace_period: Option<u32>,
+    trash_max_size: Option<i64>,
+    ratio: Option<f64>,
+    client_ratio_limit: Option<bool>,
+    min_free_space: Option<i64>,
+    target_free_space: Option<i64>,
+    free_space_order: Option<FreeSpaceOrder>,
+    rules_file: Option<String>,
+    status_address: Option<String>,
+    shutdown_timeout: Option<u32>,
+    #[serde(default, rename = "instance")]
+    instances: Vec<InstanceArgs>,
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    webhook: WebhookConfig,
+    #[serde(default)]
+    smtp: SmtpConfig,
+    #[serde(default)]
+    email: EmailConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WebhookConfig {
+    url: Option<String>,
+    preset: Option<WebhookPreset>,
+    /// Header names and values.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    template: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SmtpConfig {
+    host: Option<String>,
+    port: Option<u16>,
+    tls: Option<SmtpTls>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmailConfig {
+    from: Option<String>,
+    #[serde(default)]
+    to: Vec<String>,
+    digest: Option<DigestPeriod>,
+    alerts: Option<bool>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("Invalid config file {}: {}", path, e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut config = toml::from_str::<ConfigFile>(content).map_err(|e| e.to_string())?;
+        if config.rules_file.is_some() && !config.rules.is_empty() {
+            return Err("rules_file and [[rule]] tables can't be used together".to_string());
+        }
+        for rule in config.rules.iter_mut() {
+            rule.compile()?;
+        }
+        Ok(config)
+    }
+
+    /// Fill the values of the arguments unset by the command line and the environment.
+    pub fn apply(self, args: &mut Args) {
+        fill(&mut args.client, self.client);
+        fill(&mut args.monitoring_url, self.monitoring_url);
+        fill(&mut args.database_path, self.database_path);
+        fill(&mut args.username, self.username);
+        fill(&mut args.password, self.password);
+        fill(&mut args.scan_interval, self.scan_interval);
+        fill(&mut args.file_lifetime, self.file_lifetime);
+        fill(
+            &mut args.file_lifetime_after_copied,
+            self.file_lifetime_after_copied,
+        );
+        fill_flag(&mut args.dry_run, "FP_DRY_RUN", self.dry_run);
+        fill_flag(&mut args.keep_data, "FP_KEEP_DATA", self.keep_data);
+        fill(&mut args.stop_lifetime, self.stop_lifetime);
+        fill(&mut args.trash_dir, self.trash_dir);
+        fill(&mut args.trash_grace_period, self.trash_grace_period);
+        fill(&mut args.trash_max_size, self.trash_max_size);
+        fill(&mut args.ratio, self.ratio);
+        fill_flag(
+            &mut args.client_ratio_limit,
+            "FP_CLIENT_RATIO_LIMIT",
+            self.client_ratio_limit,
+        );
+        fill(&mut args.min_free_space, self.min_free_space);
+        fill(&mut args.target_free_space, self.target_free_space);
+        fill(&mut args.free_space_order, self.free_space_order);
+        fill(&mut args.rules_path, self.rules_file);
+        fill(&mut args.status_address, self.status_address);
+        fill(&mut args.shutdown_timeout, self.shutdown_timeout);
+        if args.instances.is_empty() {
+            args.instances = self.instances;
+        }
+        args.rules = self.rules;
+
+        fill(&mut args.webhook_url, self.webhook.url);
+        fill(&mut args.webhook_preset, self.webhook.preset);
+        if args.webhook_headers.is_empty() {
+            args.webhook_headers = self.webhook.headers.into_iter().collect();
+        }
+        fill(&mut args.webhook_template, self.webhook.template);
+
+        fill(&mut args.smtp_host, self.smtp.host);
+        fill(&mut args.smtp_port, self.smtp.port);
+        fill(&mut args.smtp_tls, self.smtp.tls);
+        fill(&mut args.smtp_username, self.smtp.username);
+        fill(&mut args.smtp_password, self.smtp.password);
+        fill(&mut args.email_from, self.email.from);
+        if args.email_to.is_empty() {
+            args.email_to = self.email.to;
+        }
+        fill(&mut args.email_digest, self.email.digest);
+        fill_flag(&mut args.email_alerts, "FP_EMAIL_ALERTS", self.email.alerts);
+    }
+}
+
+fn fill<T>(value: &mut Option<T>, file_value: Option<T>) {
+    if value.is_none() {
+        *value = file_value;
+    }
+}
+
+/// The flags can only be enabled on the command line, so the file sets them unless their
+/// environment variable does.
+fn fill_flag(value: &mut bool, env_name: &str, file_value: Option<bool>) {
+    if !*value && std::env::var(env_name).is_err() {
+        *value = file_value.unwrap_or(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = ConfigFile::parse(
+            r#"
+            client = "qbittorrent"
+            username = "admin"
+            scan_interval = 300
+            dry_run = true
+            free_space_order = "largest"
+
+            [[instance]]
+            name = "box1"
+            monitoring_url = "http://box1:8080"
+            file_lifetime = 86400
+
+            [[rule]]
+            name = "linux isos"
+            match = { name = "(?i)ubuntu" }
+            lifetime = 3600
+
+            [webhook]
+            url = "https://ntfy.sh/my-topic"
+            preset = "ntfy"
+            headers = { Priority = "high" }
+
+            [smtp]
+            host = "smtp.example.org"
+            tls = "tls"
+
+            [email]
+            from = "purge@example.org"
+            to = ["alice@example.org"]
+            digest = "weekly"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.scan_interval, Some(300));
+        assert_eq!(config.free_space_order, Some(FreeSpaceOrder::Largest));
+        assert_eq!(config.instances[0].name, "box1");
+        assert_eq!(config.instances[0].file_lifetime, Some(86400));
+        assert_eq!(config.rules[0].lifetime, Some(3600));
+        assert_eq!(config.webhook.preset, Some(WebhookPreset::Ntfy));
+        assert_eq!(config.smtp.tls, Some(SmtpTls::Tls));
+        assert_eq!(config.email.digest, Some(DigestPeriod::Weekly));
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let error = ConfigFile::parse("scan_intervall = 300").unwrap_err();
+        assert!(
+            error.contains("unknown field `scan_intervall`"),
+            "{}",
+            error
+        );
+
+        let error = ConfigFile::parse("scan_interval = \"5m\"").unwrap_err();
+        assert!(error.contains("scan_interval"), "{}", error);
+
+        let error =
+            ConfigFile::parse("[[instance]]\nname = \"box1\"\nurl = \"http://box1\"").unwrap_err();
+        assert!(error.contains("unknown field `url`"), "{}", error);
+
+        let error = ConfigFile::parse("[smtp]\ntls = \"ssl\"").unwrap_err();
+        assert!(error.contains("unknown variant `ssl`"), "{}", error);
+
+        let error = ConfigFile::parse("rules_file = \"rules.toml\"\n[[rule]]\nname = \"keep\"")
+            .unwrap_err();
+        assert!(error.contains("can't be used together"), "{}", error);
+
+        let error =
+            ConfigFile::parse("[[rule]]\nname = \"bad\"\nmatch = { name = \"(\" }").unwrap_err();
+        assert!(error.contains("Invalid name pattern"), "{}", error);
+    }
+}
@@ -1,21 +1,187 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::time::Duration;
 
-use crate::logic::api::Api;
+use crate::logic::api::TorrentClient;
+use crate::logic::api::transmission::TransmissionApi;
 use crate::logic::database::Database;
-use tokio::sync::Mutex;
+use crate::logic::database::models::{File, HistoryEntry, PlanEntry, TrashEntry};
+use crate::logic::email::{Mailer, digest_body};
+use crate::logic::history::HistoryFilter;
+use crate::logic::notify::{Event, EventKind, EventTorrent, Webhook};
+use crate::logic::plan::{
+    FreeSpacePolicy, Policy, Removal, apply_free_space_pressure, removal_mode,
+};
+use crate::logic::protection::Protections;
+use crate::logic::rules::{Rule, removal_for};
+use crate::logic::status::{ScanResult, Status, TrackedTorrent, serve};
+use crate::logic::trash::{TrashPolicy, purge};
+use futures::future::join_all;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
 
 pub mod logic;
 
+/// Name of the instance used when a single torrent client is monitored.
+pub const DEFAULT_INSTANCE: &str = "default";
+/// Time in seconds copied files are kept when no lifetime after copied is set, 5 hours.
+pub const DEFAULT_LIFETIME_AFTER_COPIED: u32 = 18000;
+
+/// Lifetimes and policies of an instance, which can be reloaded while the monitor runs.
+#[derive(Debug, Clone)]
+pub struct InstanceSettings {
+    name: String,
+    policy: Policy,
+    free_space: Option<FreeSpacePolicy>,
+    trash: Option<TrashPolicy>,
+}
+
+impl InstanceSettings {
+    pub fn new(
+        name: &str,
+        files_lifetime: Option<u32>,
+        files_lifetime_after_copied: Option<u32>,
+    ) -> Self {
+        InstanceSettings {
+            name: name.to_string(),
+            policy: Policy {
+                // Default to 7 days
+                files_lifetime: files_lifetime.unwrap_or(604800),
+                files_lifetime_after_copied: files_lifetime_after_copied
+                    .unwrap_or(DEFAULT_LIFETIME_AFTER_COPIED),
+                ratio: None,
+                client_ratio_limit: false,
+                delete_data: true,
+                trash: false,
+                stop: false,
+                stop_lifetime: None,
+            },
+            free_space: None,
+            trash: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Remove finished torrents once they reach the ratio target, or the ratio limit set on
+    /// them in the client if `client_ratio_limit` is enabled.
+    pub fn set_ratio(&mut self, ratio: Option<f64>, client_ratio_limit: bool) {
+        self.policy.ratio = ratio;
+        self.policy.client_ratio_limit = client_ratio_limit;
+    }
+
+    /// Delete the local data of the removed torrents, or only remove them from the client.
+    pub fn set_delete_data(&mut self, delete_data: bool) {
+        self.policy.delete_data = delete_data;
+    }
+
+    /// Move the data of the expired torrents to a trash directory, where it is purged after a
+    /// grace period, instead of deleting it right away.
+    pub fn set_trash(&mut self, trash: Option<TrashPolicy>) {
+        self.policy.trash = trash.is_some();
+        self.trash = trash;
+    }
+
+    /// Stop the finished torrents once their lifetime after copied expires, and only remove them
+    /// once they have been finished for the stop lifetime.
+    pub fn set_stop(&mut self, stop_lifetime: Option<u32>) {
+        self.policy.stop = stop_lifetime.is_some();
+        self.policy.stop_lifetime = stop_lifetime;
+    }
+
+    /// Remove finished torrents before their lifetime expires when the disk of their download
+    /// directory runs out of space.
+    pub fn set_free_space(&mut self, free_space: Option<FreeSpacePolicy>) {
+        self.free_space = free_space;
+    }
+}
+
+/// A torrent client monitored by the service with its own lifetimes.
+pub struct Instance {
+    settings: InstanceSettings,
+
+    api: Box<dyn TorrentClient>,
+}
+
+impl Instance {
+    pub fn new(
+        name: &str,
+        api: Box<dyn TorrentClient>,
+        files_lifetime: Option<u32>,
+        files_lifetime_after_copied: Option<u32>,
+    ) -> Self {
+        Self::with_settings(
+            InstanceSettings::new(name, files_lifetime, files_lifetime_after_copied),
+            api,
+        )
+    }
+
+    pub fn with_settings(settings: InstanceSettings, api: Box<dyn TorrentClient>) -> Self {
+        Instance { settings, api }
+    }
+
+    /// See `InstanceSettings::set_ratio`.
+    pub fn set_ratio(&mut self, ratio: Option<f64>, client_ratio_limit: bool) {
+        self.settings.set_ratio(ratio, client_ratio_limit);
+    }
+
+    /// See `InstanceSettings::set_delete_data`.
+    pub fn set_delete_data(&mut self, delete_data: bool) {
+        self.settings.set_delete_data(delete_data);
+    }
+
+    /// See `InstanceSettings::set_trash`.
+    pub fn set_trash(&mut self, trash: Option<TrashPolicy>) {
+        self.settings.set_trash(trash);
+    }
+
+    /// See `InstanceSettings::set_stop`.
+    pub fn set_stop(&mut self, stop_lifetime: Option<u32>) {
+        self.settings.set_stop(stop_lifetime);
+    }
+
+    /// See `InstanceSettings::set_free_space`.
+    pub fn set_free_space(&mut self, free_space: Option<FreeSpacePolicy>) {
+        self.settings.set_free_space(free_space);
+    }
+}
+
+/// Settings applied to a running monitor between two scan cycles, the database and the scans
+/// in progress are kept.
+pub struct Reload {
+    pub scan_interval: Option<u32>,
+    /// New lifetimes and policies of the instances, matched by name.
+    pub instances: Vec<InstanceSettings>,
+    pub rules: Vec<Rule>,
+    pub webhooks: Vec<Webhook>,
+    pub mailer: Option<Mailer>,
+    /// Configuration exposed by the status API, secrets must be redacted by the caller.
+    pub config: serde_json::Value,
+}
+
 pub struct Monitor {
     scan_interval: u32,
-    files_lifetime_after_copied: u32,
-    files_lifetime: u32,
+    dry_run: bool,
+    rules: Vec<Rule>,
 
     database: Database,
-    api: Api,
+    instances: Vec<Mutex<Instance>>,
+
+    status: Arc<Mutex<Status>>,
+    status_address: Option<String>,
+
+    webhooks: Vec<Webhook>,
+    http_client: reqwest::Client,
+    mailer: Option<Mailer>,
+
+    reloads: Option<Receiver<Reload>>,
+    stop_signal: Option<Arc<Mutex<AtomicBool>>>,
+    /// Read while torrents are removed from a client and recorded in the database.
+    removals: Arc<RwLock<()>>,
 }
 
 impl Monitor {
@@ -27,110 +193,909 @@ impl Monitor {
         files_lifetime_after_copied: Option<u32>,
         username: &str,
         password: &str,
+    ) -> Self {
+        Self::with_client(
+            Box::new(
+                TransmissionApi::new(username.to_string(), password.to_string(), monitoring_url)
+                    .expect("Invalid API URL"),
+            ),
+            database_path,
+            scan_interval,
+            files_lifetime,
+            files_lifetime_after_copied,
+        )
+    }
+
+    pub fn with_client(
+        api: Box<dyn TorrentClient>,
+        database_path: Option<String>,
+        scan_interval: Option<u32>,
+        files_lifetime: Option<u32>,
+        files_lifetime_after_copied: Option<u32>,
+    ) -> Self {
+        Self::with_instances(
+            vec![Instance::new(
+                DEFAULT_INSTANCE,
+                api,
+                files_lifetime,
+                files_lifetime_after_copied,
+            )],
+            database_path,
+            scan_interval,
+        )
+    }
+
+    pub fn with_instances(
+        instances: Vec<Instance>,
+        database_path: Option<String>,
+        scan_interval: Option<u32>,
     ) -> Self {
         Monitor {
             // Default to 60 seconds
             scan_interval: scan_interval.unwrap_or(60),
-            // Default to 7 days
-            files_lifetime: files_lifetime.unwrap_or(604800),
-            // Default to 5 hours
-            files_lifetime_after_copied: files_lifetime_after_copied.unwrap_or(18000),
-
-            api: Api::new(username.to_string(), password.to_string(), monitoring_url),
+            dry_run: false,
+            rules: vec![],
 
             database: Database::new(database_path),
+            instances: instances.into_iter().map(Mutex::new).collect(),
+
+            status: Arc::new(Mutex::new(Status::default())),
+            status_address: None,
+
+            webhooks: vec![],
+            http_client: reqwest::Client::new(),
+            mailer: None,
+
+            reloads: None,
+            stop_signal: None,
+            removals: Arc::new(RwLock::new(())),
         }
     }
 
+    /// Only compute and persist the removal plan, never delete anything.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Rules applied in order to every scanned torrent, the first matching rule wins.
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        self.rules = rules;
+    }
+
+    /// Serve the status API on this address, e.g. `0.0.0.0:8080`, while the monitor runs.
+    pub fn set_status_address(&mut self, status_address: Option<String>) {
+        self.status_address = status_address;
+    }
+
+    /// Webhooks notified when torrents are removed, fail to be removed, or a scan fails.
+    pub fn set_webhooks(&mut self, webhooks: Vec<Webhook>) {
+        self.webhooks = webhooks;
+    }
+
+    /// Send the email digests and alerts through this mailer.
+    pub fn set_mailer(&mut self, mailer: Option<Mailer>) {
+        self.mailer = mailer;
+    }
+
+    /// Lock read by the removals in progress. Once its write lock is held, no torrent is being
+    /// removed, so the monitor can be aborted without losing track of a removal.
+    pub fn removals(&self) -> Arc<RwLock<()>> {
+        self.removals.clone()
+    }
+
+    /// Apply the settings received on this channel while the monitor runs.
+    pub fn set_reloads(&mut self, reloads: Option<Receiver<Reload>>) {
+        self.reloads = reloads;
+    }
+
+    /// Apply new settings, the instances can't be added, removed or renamed. The current
+    /// settings are kept if the new ones are rejected.
+    pub async fn reload(&mut self, reload: Reload) -> Result<(), String> {
+        let mut current = vec![];
+        for instance in &self.instances {
+            current.push(instance.lock().await.settings.name.clone());
+        }
+        let mut names: Vec<String> = reload
+            .instances
+            .iter()
+            .map(|settings| settings.name.clone())
+            .collect();
+        current.sort();
+        names.sort();
+        if current != names {
+            return Err(format!(
+                "Instances can't change without a restart, expected {} but got {}",
+                current.join(", "),
+                names.join(", ")
+            ));
+        }
+
+        for settings in reload.instances {
+            for instance in &self.instances {
+                let mut instance = instance.lock().await;
+                if instance.settings.name == settings.name {
+                    instance.settings = settings;
+                    break;
+                }
+            }
+        }
+        // Default to 60 seconds
+        self.scan_interval = reload.scan_interval.unwrap_or(60);
+        self.rules = reload.rules;
+        self.webhooks = reload.webhooks;
+        self.mailer = reload.mailer;
+
+        let mut status = self.status.lock().await;
+        status.set_scan_interval(self.scan_interval);
+        status.set_config(reload.config);
+        Ok(())
+    }
+
+    /// State exposed by the status API, updated after every scan.
+    pub fn status(&self) -> Arc<Mutex<Status>> {
+        self.status.clone()
+    }
+
+    /// Scan the instances until the stop signal is set. The scan in progress when it is set
+    /// finishes without starting new removals, then the database is closed.
     pub async fn run(&mut self, stop_signal: Option<Arc<Mutex<AtomicBool>>>) {
         let mut scan_interval_it = 0;
+        self.stop_signal = stop_signal;
 
         self.database
             .connect()
             .await
             .expect("Failed to connect to database");
 
-        loop {
-            if let Some(signal) = &stop_signal {
-                if signal
-                    .lock()
-                    .await
-                    .load(std::sync::atomic::Ordering::SeqCst)
-                {
-                    break;
+        let instances: Vec<String> = join_all(
+            self.instances
+                .iter()
+                .map(|instance| async { instance.lock().await.settings.name.clone() }),
+        )
+        .await;
+        self.status
+            .lock()
+            .await
+            .start(&instances, self.scan_interval, current_timestamp());
+
+        let mut digest_period = self.digest_period();
+        let mut next_digest = self.next_digest(digest_period).await;
+        let mut reloads = self.reloads.take();
+
+        let mut status_server = None;
+        if let Some(address) = &self.status_address {
+            match TcpListener::bind(address).await {
+                Ok(listener) => {
+                    println!("Status API listening on {}", address);
+                    status_server = Some(tokio::spawn(serve(listener, self.status.clone())));
+                }
+                Err(e) => {
+                    println!("Failed to start the status API on {}: {}", address, e);
                 }
             }
+        }
 
-            if scan_interval_it >= self.scan_interval {
-                match self.scan_files_and_cleanup().await {
-                    Ok(_) => {}
+        loop {
+            if self.stopping().await {
+                break;
+            }
+
+            // settings are only reloaded between two scan cycles
+            if let Some(receiver) = &mut reloads
+                && let Ok(reload) = receiver.try_recv()
+            {
+                match self.reload(reload).await {
+                    Ok(_) => {
+                        println!("Configuration reloaded");
+                        if self.digest_period() != digest_period {
+                            digest_period = self.digest_period();
+                            next_digest = self.next_digest(digest_period).await;
+                        }
+                    }
                     Err(e) => {
-                        println!("Error during scan and cleanup: {}", e);
+                        println!("Configuration not reloaded, keeping the current one: {}", e)
                     }
                 }
+            }
+
+            if scan_interval_it >= self.scan_interval {
+                // scan all the instances concurrently
+                join_all(
+                    self.instances
+                        .iter()
+                        .map(|instance| self.scan_instance(instance)),
+                )
+                .await;
                 scan_interval_it = 0;
             }
 
+            if let (Some(period), Some(date)) = (digest_period, next_digest)
+                && current_timestamp() >= date
+            {
+                let to = current_timestamp();
+                self.send_digest(date - period, to).await;
+                self.database.set_last_digest_date(to).await;
+                next_digest = Some(to + period);
+            }
+
             tokio::time::sleep(Duration::from_secs(1)).await;
             scan_interval_it += 1;
         }
+
+        if let Some(server) = status_server {
+            server.abort();
+        }
+        self.reloads = reloads;
+        if let Err(e) = self.database.close().await {
+            println!("{}", e);
+        }
     }
 
-    async fn scan_files_and_cleanup(&mut self) -> Result<(), String> {
+    /// Start a batch of removals unless the service is shutting down, the batch is done and
+    /// recorded before the returned guard is dropped.
+    async fn start_removals(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        if self.stopping().await {
+            return None;
+        }
+        Some(self.removals.read().await)
+    }
+
+    /// The stop signal is set, the service is shutting down.
+    async fn stopping(&self) -> bool {
+        match &self.stop_signal {
+            Some(signal) => signal
+                .lock()
+                .await
+                .load(std::sync::atomic::Ordering::SeqCst),
+            None => false,
+        }
+    }
+
+    /// Date of the next email digest, one period after the last one. The date of the last digest
+    /// is kept in the database, so restarts and reloads don't postpone it.
+    async fn next_digest(&self, period: Option<i64>) -> Option<i64> {
+        let period = period?;
+        let last = match self.database.last_digest_date().await {
+            Some(last) => last,
+            None => {
+                // the first digest covers the removals from now on
+                let now = current_timestamp();
+                self.database.set_last_digest_date(now).await;
+                now
+            }
+        };
+        Some(last + period)
+    }
+
+    /// Time in seconds between two email digests, if enabled.
+    fn digest_period(&self) -> Option<i64> {
+        self.mailer
+            .as_ref()
+            .and_then(|mailer| mailer.settings().digest)
+            .map(|period| period.seconds())
+    }
+
+    async fn scan_instance(&self, instance: &Mutex<Instance>) {
+        let mut instance = instance.lock().await;
+        let start = std::time::Instant::now();
+        let mut scan = ScanResult {
+            instance: instance.settings.name.clone(),
+            started_date: current_timestamp(),
+            ..Default::default()
+        };
+        match self.scan_files_and_cleanup(&mut instance, &mut scan).await {
+            Ok(_) => {
+                scan.success = true;
+            }
+            Err(e) => {
+                println!(
+                    "[{}] Error during scan and cleanup: {}",
+                    instance.settings.name, e
+                );
+                // only the first failure is notified, not every scan while the client is down
+                if !self
+                    .status
+                    .lock()
+                    .await
+                    .scan_failed(&instance.settings.name)
+                {
+                    self.notify(Event {
+                        event: EventKind::ScanFailed,
+                        instance: instance.settings.name.clone(),
+                        date: scan.started_date,
+                        mode: None,
+                        torrents: vec![],
+                        error: Some(e.clone()),
+                    })
+                    .await;
+                }
+                scan.error = Some(e);
+            }
+        }
+        scan.duration_ms = start.elapsed().as_millis() as u64;
+        self.status.lock().await.set_scan(scan);
+    }
+
+    async fn scan_files_and_cleanup(
+        &self,
+        instance: &mut Instance,
+        scan: &mut ScanResult,
+    ) -> Result<(), String> {
         // Fetch files from API and update database
-        let files = self.api.fetch_files().await.expect("Failed to fetch files");
+        let files = match instance.api.fetch_files().await {
+            Ok(files) => files,
+            Err(e) => {
+                self.record_rpc_error(&instance.settings.name, "fetch")
+                    .await;
+                return Err(e);
+            }
+        };
         let mut updated_files_ids: Vec<i32> = vec![];
-        for file in files {
-            updated_files_ids.push(self.database.create_or_update_file(file).await);
+        let mut fetched_files: HashMap<i32, File> = HashMap::new();
+        for mut file in files {
+            file.instance = instance.settings.name.clone();
+            // files are tracked by hash, their server id is updated on every scan
+            let id = self.database.create_or_update_file(file.clone()).await;
+            updated_files_ids.push(id);
+            fetched_files.insert(id, file);
         }
 
         // Remove files that are no longer present
         self.database
-            .remove_no_matching_files_ids(&updated_files_ids)
+            .remove_no_matching_files_ids(&instance.settings.name, &updated_files_ids)
             .await;
 
         // Cleanup old files based on lifetime
-        let files_id = self.database.list_of_file_ids().await;
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        // Remove files older copied files
-        let mut already_removed_files: HashSet<i32> = HashSet::new();
-        let mut files_to_remove: Vec<i32> = vec![];
-        for file in files_id.clone() {
-            if let Some(finish_date) = file.finish_date {
-                if current_time - finish_date > self.files_lifetime_after_copied as i64 {
-                    files_to_remove.push(file.server_id);
-                    already_removed_files.insert(file.id);
+        let files_id = self
+            .database
+            .list_of_file_ids(&instance.settings.name)
+            .await;
+        let current_time = current_timestamp();
+
+        // Compute when each file expires, protected files are never removed
+        let protections = Protections::new(&self.database.list_protections().await);
+        let mut protected: Vec<File> = vec![];
+        let mut removals: Vec<Removal> = files_id
+            .into_iter()
+            .filter_map(|file| {
+                // the metadata used by the rules is only known by the torrent client
+                let file = match fetched_files.remove(&file.id) {
+                    Some(fetched) => File {
+                        id: file.id,
+                        added_date: file.added_date,
+                        finish_date: file.finish_date,
+                        stopped_date: file.stopped_date,
+                        ..fetched
+                    },
+                    None => file,
+                };
+                if protections.is_protected(&file) {
+                    protected.push(file);
+                    return None;
                 }
+                removal_for(
+                    &self.rules,
+                    &instance.settings.name,
+                    &file,
+                    &instance.settings.policy,
+                    current_time,
+                )
+            })
+            .collect();
+        if let Some(free_space) = instance.settings.free_space {
+            self.apply_free_space_pressure(instance, &mut removals, &free_space, current_time)
+                .await;
+        }
+
+        scan.tracked = removals.len() + protected.len();
+        scan.due = removals
+            .iter()
+            .filter(|removal| removal.is_due(current_time))
+            .count();
+        scan.pending = removals.len() - scan.due;
+        let torrents = removals
+            .iter()
+            .map(|removal| TrackedTorrent::from_removal(&instance.settings.name, removal))
+            .chain(
+                protected
+                    .iter()
+                    .map(|file| TrackedTorrent::protected(&instance.settings.name, file)),
+            )
+            .collect();
+        self.status
+            .lock()
+            .await
+            .set_torrents(&instance.settings.name, torrents);
+
+        if self.dry_run {
+            self.save_plan(&instance.settings.name, &removals, current_time)
+                .await;
+            return Ok(());
+        }
+
+        // the due removals are done by the next scan after a restart rather than interrupted
+        if self.stopping().await {
+            println!(
+                "[{}] Shutting down, removals skipped",
+                instance.settings.name
+            );
+            return Ok(());
+        }
+
+        self.stop_files(instance, &removals, current_time).await;
+
+        if let Some(trash) = instance.settings.trash.clone() {
+            let trashed: Vec<&Removal> = removals
+                .iter()
+                .filter(|removal| removal.is_removal_due(current_time) && removal.trash)
+                .collect();
+            self.trash_files(instance, &trash, &trashed, current_time)
+                .await;
+        }
+
+        for delete_data in [true, false] {
+            let removed: Vec<&Removal> = removals
+                .iter()
+                .filter(|removal| {
+                    removal.is_removal_due(current_time)
+                        && !removal.trash
+                        && removal.delete_data == delete_data
+                })
+                .collect();
+            let files_to_remove: Vec<&File> = removed.iter().map(|removal| &removal.file).collect();
+            if files_to_remove.is_empty() {
+                continue;
             }
+            if !delete_data && !instance.api.capabilities().keep_data {
+                println!(
+                    "[{}] Can not remove files without deleting their data: {}",
+                    instance.settings.name,
+                    identities(&files_to_remove)
+                );
+                continue;
+            }
+
+            let mode = removal_mode(delete_data);
+            let hashes: Vec<String> = files_to_remove
+                .iter()
+                .map(|file| file.hash.clone())
+                .collect();
+            // the torrent clients only get the hashes, the names are logged here
+            println!(
+                "[{}] Removing files ({}): {}",
+                instance.settings.name,
+                mode,
+                identities(&files_to_remove)
+            );
+            let Some(removing) = self.start_removals().await else {
+                return Ok(());
+            };
+            let result = instance.api.delete_file(&hashes, delete_data).await;
+            self.record_history(
+                &instance.settings.name,
+                &removed,
+                mode,
+                &result,
+                current_time,
+            )
+            .await;
+            match &result {
+                Ok(_) => {
+                    println!(
+                        "[{}] Successfully removed files ({}): {}",
+                        instance.settings.name,
+                        mode,
+                        identities(&files_to_remove)
+                    );
+                    self.record_deleted(&instance.settings.name, &removed, mode)
+                        .await;
+                }
+                Err(e) => {
+                    self.record_rpc_error(&instance.settings.name, "delete")
+                        .await;
+                    println!(
+                        "[{}] Failed to remove files ({}): {}, error: {}",
+                        instance.settings.name,
+                        mode,
+                        identities(&files_to_remove),
+                        e
+                    );
+                }
+            }
+            drop(removing);
+            // notified once the database is up to date, the webhook or mail server can be slow
+            self.notify_removal(
+                &instance.settings.name,
+                &removed,
+                mode,
+                &result,
+                current_time,
+            )
+            .await;
         }
 
-        // Remove files older than lifetime
-        for file in files_id {
-            if current_time - file.added_date > self.files_lifetime as i64
-                && !already_removed_files.contains(&file.id)
-            {
-                files_to_remove.push(file.server_id);
+        if let Some(trash) = &instance.settings.trash {
+            self.purge_trash(&instance.settings.name, trash, current_time)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Stop the torrents whose lifetime after copied expired, torrents are only stopped once so
+    /// they can be resumed by hand.
+    async fn stop_files(&self, instance: &mut Instance, removals: &[Removal], current_time: i64) {
+        let stopped: Vec<&File> = removals
+            .iter()
+            .filter(|removal| {
+                removal.stop && removal.is_due(current_time) && removal.file.stopped_date.is_none()
+            })
+            .map(|removal| &removal.file)
+            .collect();
+        if stopped.is_empty() {
+            return;
+        }
+        let hashes: Vec<String> = stopped.iter().map(|file| file.hash.clone()).collect();
+        if !instance.api.capabilities().stop {
+            println!(
+                "[{}] Can not stop files, the torrent client does not support it: {}",
+                instance.settings.name,
+                identities(&stopped)
+            );
+            return;
+        }
+
+        let Some(_removing) = self.start_removals().await else {
+            return;
+        };
+        match instance.api.stop_file(&hashes).await {
+            Ok(_) => {
+                println!(
+                    "[{}] Successfully stopped files: {}",
+                    instance.settings.name,
+                    identities(&stopped)
+                );
+                for file in stopped {
+                    self.database.set_file_stopped(file.id, current_time).await;
+                }
+            }
+            Err(e) => {
+                self.record_rpc_error(&instance.settings.name, "stop").await;
+                println!(
+                    "[{}] Failed to stop files: {}, error: {}",
+                    instance.settings.name,
+                    identities(&stopped),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Move the data of the torrents to the trash and remove them from the client.
+    async fn trash_files(
+        &self,
+        instance: &mut Instance,
+        trash: &TrashPolicy,
+        removals: &[&Removal],
+        current_time: i64,
+    ) {
+        if removals.is_empty() {
+            return;
+        }
+        if !instance.api.capabilities().move_data {
+            println!(
+                "[{}] Can not move files to the trash, the torrent client does not support it",
+                instance.settings.name
+            );
+            return;
+        }
+
+        for removal in removals {
+            let server_id = removal.file.server_id;
+            let hashes = [removal.file.hash.clone()];
+            let location = trash.location(server_id, current_time);
+            let Some(removing) = self.start_removals().await else {
+                return;
+            };
+            let result = match instance.api.move_file(&hashes, &location).await {
+                Ok(_) => instance.api.delete_file(&hashes, false).await,
+                Err(e) => Err(e),
+            };
+            self.record_history(
+                &instance.settings.name,
+                &[removal],
+                "trash",
+                &result,
+                current_time,
+            )
+            .await;
+            match &result {
+                Ok(_) => {
+                    println!(
+                        "[{}] Successfully moved file {} to the trash: {}",
+                        instance.settings.name, removal.file, location
+                    );
+                    self.record_deleted(&instance.settings.name, &[removal], "trash")
+                        .await;
+                    self.database
+                        .add_trash_entry(&TrashEntry {
+                            id: 0,
+                            instance: instance.settings.name.clone(),
+                            name: removal.file.name.clone(),
+                            path: location,
+                            size: removal.file.size,
+                            trashed_date: current_time,
+                        })
+                        .await;
+                }
+                Err(e) => {
+                    self.record_rpc_error(&instance.settings.name, "trash")
+                        .await;
+                    println!(
+                        "[{}] Failed to move file {} to the trash, error: {}",
+                        instance.settings.name, removal.file, e
+                    );
+                }
             }
+            drop(removing);
+            // notified last, like the other removals
+            self.notify_removal(
+                &instance.settings.name,
+                &[removal],
+                "trash",
+                &result,
+                current_time,
+            )
+            .await;
+        }
+    }
+
+    /// Append the removals to the deletion history with the result of the torrent client.
+    async fn record_history(
+        &self,
+        instance: &str,
+        removals: &[&Removal],
+        mode: &str,
+        result: &Result<(), String>,
+        current_time: i64,
+    ) {
+        for removal in removals {
+            self.database
+                .add_history_entry(&HistoryEntry {
+                    id: 0,
+                    instance: instance.to_string(),
+                    hash: removal.file.hash.clone(),
+                    name: removal.file.name.clone(),
+                    size: removal.file.size,
+                    reason: removal.reason.as_str().to_string(),
+                    rule: removal.rule.clone(),
+                    mode: mode.to_string(),
+                    deleted_date: current_time,
+                    result: match result {
+                        Ok(_) => "success".to_string(),
+                        Err(e) => e.clone(),
+                    },
+                })
+                .await;
         }
+    }
+
+    async fn notify_removal(
+        &self,
+        instance: &str,
+        removals: &[&Removal],
+        mode: &str,
+        result: &Result<(), String>,
+        current_time: i64,
+    ) {
+        self.notify(Event {
+            event: match result {
+                Ok(_) => EventKind::Deleted,
+                Err(_) => EventKind::DeleteFailed,
+            },
+            instance: instance.to_string(),
+            date: current_time,
+            mode: Some(mode.to_string()),
+            torrents: removals
+                .iter()
+                .map(|removal| EventTorrent::from_removal(removal))
+                .collect(),
+            error: result.clone().err(),
+        })
+        .await;
+    }
 
-        if !files_to_remove.is_empty() {
-            match self.api.delete_file(&files_to_remove).await {
+    /// Email the removals between the two dates and the torrents expiring in the next 24 hours.
+    async fn send_digest(&self, from: i64, to: i64) {
+        let Some(mailer) = &self.mailer else {
+            return;
+        };
+        let history = self
+            .database
+            .list_history(&HistoryFilter {
+                from: Some(from),
+                to: Some(to),
+                name: None,
+            })
+            .await;
+        let body = {
+            let status = self.status.lock().await;
+            let mut expiring: Vec<&TrackedTorrent> = status
+                .torrents()
+                .into_iter()
+                .filter(|torrent| {
+                    torrent
+                        .expiry_date
+                        .is_some_and(|date| date > to && date <= to + 86400)
+                })
+                .collect();
+            expiring.sort_by_key(|torrent| torrent.expiry_date);
+            digest_body(&history, &expiring, from, to)
+        };
+
+        match mailer.send("file_purge digest", body).await {
+            Ok(_) => println!("Sent the email digest"),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    /// Send the event to every webhook, and failures to the mailer if alerts are enabled.
+    /// Failures to notify are only logged.
+    async fn notify(&self, event: Event) {
+        if let Some(mailer) = &self.mailer
+            && mailer.settings().alerts
+            && event.event != EventKind::Deleted
+        {
+            let subject = format!("file_purge: {} on {}", event.event.as_str(), event.instance);
+            if let Err(e) = mailer.send(&subject, event.message()).await {
+                println!("[{}] {}", event.instance, e);
+            }
+        }
+        for webhook in &self.webhooks {
+            if let Err(e) = webhook.send(&self.http_client, &event).await {
+                println!(
+                    "[{}] Failed to send the {} webhook: {}",
+                    event.instance,
+                    webhook.preset.as_str(),
+                    e
+                );
+            }
+        }
+    }
+
+    async fn record_rpc_error(&self, instance: &str, operation: &str) {
+        self.status
+            .lock()
+            .await
+            .metrics_mut()
+            .record_rpc_error(instance, operation);
+    }
+
+    async fn record_deleted(&self, instance: &str, removals: &[&Removal], mode: &str) {
+        let mut status = self.status.lock().await;
+        for removal in removals {
+            status.metrics_mut().record_deleted(
+                instance,
+                removal.reason.as_str(),
+                mode,
+                removal.file.size,
+            );
+        }
+    }
+
+    /// Delete for good the trash entries past the grace period or beyond the size cap.
+    async fn purge_trash(&self, instance: &str, trash: &TrashPolicy, current_time: i64) {
+        let entries = self.database.list_trash(instance).await;
+        for entry in trash.entries_to_purge(&entries, current_time) {
+            let Some(_removing) = self.start_removals().await else {
+                return;
+            };
+            match purge(entry) {
                 Ok(_) => {
-                    println!("Successfully deleted files: {:?}", files_to_remove);
+                    println!("[{}] Purged {} from the trash", instance, entry.path);
+                    self.database.remove_trash_entry(entry.id).await;
+                    self.status
+                        .lock()
+                        .await
+                        .metrics_mut()
+                        .record_purged(instance, entry.size);
                 }
                 Err(e) => {
+                    println!("[{}] {}", instance, e);
+                }
+            }
+        }
+    }
+
+    async fn apply_free_space_pressure(
+        &self,
+        instance: &mut Instance,
+        removals: &mut [Removal],
+        policy: &FreeSpacePolicy,
+        current_time: i64,
+    ) {
+        if !instance.api.capabilities().free_space {
+            return;
+        }
+
+        let mut directories: Vec<String> = removals
+            .iter()
+            .map(|removal| removal.file.download_dir.clone())
+            .filter(|directory| !directory.is_empty())
+            .collect();
+        directories.sort();
+        directories.dedup();
+
+        let mut free_space: HashMap<String, i64> = HashMap::new();
+        for directory in directories {
+            match instance.api.free_space(&directory).await {
+                Ok(free) => {
+                    if free < policy.min_free_space {
+                        println!(
+                            "[{}] Low free space in {}: {} bytes",
+                            instance.settings.name, directory, free
+                        );
+                    }
+                    free_space.insert(directory, free);
+                }
+                Err(e) => {
+                    self.record_rpc_error(&instance.settings.name, "free_space")
+                        .await;
                     println!(
-                        "Failed to delete files: {:?}, error: {}",
-                        files_to_remove, e
+                        "[{}] Failed to get free space of {}: {}",
+                        instance.settings.name, directory, e
                     );
                 }
             }
         }
 
-        Ok(())
+        apply_free_space_pressure(removals, &free_space, policy, current_time);
+    }
+
+    /// Print and persist what would be deleted instead of deleting it.
+    async fn save_plan(&self, instance: &str, removals: &[Removal], current_time: i64) {
+        let mut entries: Vec<PlanEntry> = vec![];
+        for removal in removals {
+            if removal.is_due(current_time) {
+                println!(
+                    "[{}] Dry run, would {} file {} ({}, {}, rule {}, expired at {})",
+                    instance,
+                    if removal.stop { "stop" } else { "remove" },
+                    removal.file,
+                    removal.mode(),
+                    removal.reason.as_str(),
+                    removal.rule.as_deref().unwrap_or("none"),
+                    removal.due_date
+                );
+            }
+            entries.push(PlanEntry {
+                id: 0,
+                instance: instance.to_string(),
+                server_id: removal.file.server_id,
+                name: removal.file.name.clone(),
+                reason: removal.reason.as_str().to_string(),
+                due_date: removal.due_date,
+                planned_date: current_time,
+                rule: removal.rule.clone(),
+                mode: removal.mode().to_string(),
+            });
+        }
+        self.database.replace_plan(instance, &entries).await;
     }
 }
+
+fn current_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Readable list of the torrents for the logs.
+fn identities(files: &[&File]) -> String {
+    files
+        .iter()
+        .map(|file| file.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
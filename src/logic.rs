@@ -1,2 +1,11 @@
-pub mod database;
 pub mod api;
+pub mod database;
+pub mod email;
+pub mod history;
+pub mod metrics;
+pub mod notify;
+pub mod plan;
+pub mod protection;
+pub mod rules;
+pub mod status;
+pub mod trash;
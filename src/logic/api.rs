@@ -1,87 +1,101 @@
-use transmission_rpc::TransClient;
-use transmission_rpc::types::Id::Id;
-use transmission_rpc::types::{BasicAuth, TorrentGetField};
-use url::Url;
+use std::collections::HashMap;
 
+use crate::logic::api::deluge::DelugeApi;
+use crate::logic::api::qbittorrent::QBittorrentApi;
+use crate::logic::api::rtorrent::RTorrentApi;
+use crate::logic::api::transmission::TransmissionApi;
 use crate::logic::database::models::File;
+use url::Url;
+
+pub mod deluge;
+pub mod qbittorrent;
+pub mod rtorrent;
+pub mod transmission;
 
-pub struct Api {
-    client: Option<TransClient>,
+/// Optional features supported by a torrent client backend.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    /// The client reports when a torrent finished downloading.
+    pub completion_date: bool,
+    /// The client can remove a torrent while keeping its local data.
+    pub keep_data: bool,
+    /// The client reports the free space of its download directories.
+    pub free_space: bool,
+    /// The client can move the data of a torrent to another directory.
+    pub move_data: bool,
+    /// The client can stop a torrent without removing it.
+    pub stop: bool,
 }
 
-impl Api {
-    pub fn new(username: String, password: String, api_url: &str) -> Self {
-        Api {
-            client: Some(TransClient::with_auth(
-                Url::parse(api_url).expect("Invalid API URL"),
-                BasicAuth {
-                    user: username,
-                    password,
-                },
-            )),
-        }
-    }
+/// Backend used by the monitor to talk to a torrent client.
+#[async_trait::async_trait]
+pub trait TorrentClient: Send {
+    /// List the torrents currently present in the client.
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String>;
 
-    pub async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
-        let list = self
-            .client
-            .as_mut()
-            .unwrap()
-            .torrent_get(
-                Some(vec![
-                    TorrentGetField::Id,
-                    TorrentGetField::AddedDate,
-                    TorrentGetField::IsFinished,
-                    TorrentGetField::PercentDone,
-                ]),
-                None,
-            )
-            .await
-            .expect("Failed to fetch torrents from Transmission API");
+    /// Remove the torrents with the given info-hashes, and their local data if `delete_data`.
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String>;
 
-        let mut files: Vec<File> = vec![];
-        for item in list.arguments.torrents {
-            files.push(File {
-                id: 0,
-                server_id: item.id.expect("Missing torrent ID") as i32,
-                added_date: item.added_date.expect("Missing addedDate").timestamp(),
-                finish_date: if item.is_finished.expect("Missing isFinished value")
-                    || item.percent_done.expect("Missing percentDone") >= 1.0
-                {
-                    let millis = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64;
-                    Some(millis)
-                } else {
-                    None
-                },
-            });
-        }
+    /// Move the data of the torrents with the given info-hashes into the given directory.
+    async fn move_file(&mut self, _hashes: &[String], _location: &str) -> Result<(), String> {
+        Err("Moving data is not supported by this torrent client".to_string())
+    }
 
-        Ok(files)
+    /// Stop the torrents with the given info-hashes, they stay in the client.
+    async fn stop_file(&mut self, _hashes: &[String]) -> Result<(), String> {
+        Err("Stopping torrents is not supported by this torrent client".to_string())
     }
 
-    pub async fn delete_file(&mut self, ids: &Vec<i32>) -> Result<(), String> {
-        println!("Deleting files with IDs: {:?}", ids);
-        println!(
-            "Deleting files with IDs: {:?}",
-            ids.iter().map(|&id| Id(id as i64)).collect::<Vec<_>>()
-        );
+    /// Free space in bytes of the disk holding the given download directory.
+    async fn free_space(&mut self, _path: &str) -> Result<i64, String> {
+        Err("Free space is not supported by this torrent client".to_string())
+    }
 
-        let res = self
-            .client
-            .as_mut()
-            .unwrap()
-            .torrent_remove(ids.iter().map(|&id| Id(id as i64)).collect(), true)
-            .await
-            .expect("Failed to delete file from Transmission API");
+    fn capabilities(&self) -> Capabilities;
+}
 
-        println!("Delete response: {:?}", res);
+/// Assigns numeric server ids to torrents of clients that identify them by hash.
+#[derive(Default)]
+pub(crate) struct HashIds {
+    ids: HashMap<String, i32>,
+    next_id: i32,
+}
 
-        if res.result != "success" {
-            return Err(format!("Failed to delete files: {}", res.result));
+impl HashIds {
+    pub(crate) fn id_for(&mut self, hash: &str) -> i32 {
+        if let Some(id) = self.ids.get(hash) {
+            return *id;
         }
-        Ok(())
+        self.next_id += 1;
+        self.ids.insert(hash.to_string(), self.next_id);
+        self.next_id
+    }
+}
+
+/// Extract the host of a tracker announce url.
+pub(crate) fn tracker_host(announce: &str) -> Option<String> {
+    url::Url::parse(announce)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+}
+
+/// Parse the URL of a torrent client.
+pub(crate) fn parse_url(api_url: &str) -> Result<Url, String> {
+    Url::parse(api_url).map_err(|e| format!("Invalid API URL '{}': {}", api_url, e))
+}
+
+/// Build the backend for the given client name.
+pub fn new_client(
+    client: &str,
+    username: String,
+    password: String,
+    api_url: &str,
+) -> Result<Box<dyn TorrentClient>, String> {
+    match client {
+        "transmission" => Ok(Box::new(TransmissionApi::new(username, password, api_url)?)),
+        "qbittorrent" => Ok(Box::new(QBittorrentApi::new(username, password, api_url)?)),
+        "deluge" => Ok(Box::new(DelugeApi::new(password, api_url)?)),
+        "rtorrent" => Ok(Box::new(RTorrentApi::new(username, password, api_url)?)),
+        _ => Err(format!("Unsupported torrent client: {}", client)),
     }
 }
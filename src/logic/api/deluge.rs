@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::logic::api::{Capabilities, HashIds, TorrentClient, parse_url};
+use crate::logic::database::models::{File, SEED_RATIO_MODE_SINGLE, SEED_RATIO_MODE_UNLIMITED};
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    message: String,
+    code: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    result: Value,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TorrentStatus {
+    time_added: f64,
+    #[serde(default)]
+    completed_time: f64,
+    progress: f64,
+    is_finished: bool,
+    #[serde(default)]
+    ratio: f64,
+    #[serde(default)]
+    stop_at_ratio: bool,
+    #[serde(default)]
+    stop_ratio: f64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    total_size: i64,
+    #[serde(default)]
+    save_path: String,
+    /// Only reported when the label plugin is enabled.
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    tracker_host: String,
+    #[serde(default)]
+    private: bool,
+}
+
+/// Deluge error code returned when the session is not authenticated.
+const NOT_AUTHENTICATED: i64 = 1;
+
+pub struct DelugeApi {
+    client: reqwest::Client,
+    api_url: Url,
+    password: String,
+    session_cookie: Option<String>,
+    request_id: u64,
+    ids: HashIds,
+}
+
+impl DelugeApi {
+    pub fn new(password: String, api_url: &str) -> Result<Self, String> {
+        let mut api_url = parse_url(api_url)?;
+        if !api_url.path().ends_with("/json") {
+            api_url = api_url
+                .join(format!("{}/json", api_url.path().trim_end_matches('/')).as_str())
+                .map_err(|e| format!("Invalid API URL '{}': {}", api_url, e))?;
+        }
+
+        Ok(DelugeApi {
+            client: reqwest::Client::new(),
+            api_url,
+            password,
+            session_cookie: None,
+            request_id: 0,
+            ids: HashIds::default(),
+        })
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<RpcResponse, String> {
+        self.request_id += 1;
+        let mut request = self.client.post(self.api_url.clone()).json(&json!({
+            "method": method,
+            "params": params,
+            "id": self.request_id,
+        }));
+        if let Some(cookie) = &self.session_cookie {
+            request = request.header("Cookie", cookie);
+        }
+
+        let res = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call Deluge API: {}", e))?;
+        if !res.status().is_success() {
+            return Err(format!("Deluge API returned status {}", res.status()));
+        }
+
+        if let Some(cookie) = res
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.split(';').next())
+            .find(|value| value.starts_with("_session_id="))
+        {
+            self.session_cookie = Some(cookie.to_string());
+        }
+
+        res.json()
+            .await
+            .map_err(|e| format!("Failed to parse Deluge API response: {}", e))
+    }
+
+    async fn login(&mut self) -> Result<(), String> {
+        let res = self
+            .request("auth.login", json!([self.password.clone()]))
+            .await?;
+        if res.result != Value::Bool(true) {
+            return Err("Failed to login into Deluge API: invalid password".to_string());
+        }
+
+        // make sure the web UI is connected to a daemon
+        let connected = self.request("web.connected", json!([])).await?;
+        if connected.result != Value::Bool(true) {
+            let hosts = self.request("web.get_hosts", json!([])).await?;
+            let host_id = hosts
+                .result
+                .get(0)
+                .and_then(|host| host.get(0))
+                .cloned()
+                .ok_or("Deluge web UI has no daemon configured")?;
+            self.request("web.connect", json!([host_id])).await?;
+        }
+        Ok(())
+    }
+
+    /// Call a method, login again once if the session is not authenticated.
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        if self.session_cookie.is_none() {
+            self.login().await?;
+        }
+
+        let mut res = self.request(method, params.clone()).await?;
+        if let Some(RpcError { code, .. }) = res.error
+            && code == NOT_AUTHENTICATED
+        {
+            self.login().await?;
+            res = self.request(method, params).await?;
+        }
+
+        match res.error {
+            Some(error) => Err(format!("Deluge API error: {}", error.message)),
+            None => Ok(res.result),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for DelugeApi {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        let result = self
+            .call(
+                "core.get_torrents_status",
+                json!([
+                    {},
+                    [
+                        "time_added",
+                        "completed_time",
+                        "progress",
+                        "is_finished",
+                        "ratio",
+                        "stop_at_ratio",
+                        "stop_ratio",
+                        "name",
+                        "total_size",
+                        "save_path",
+                        "label",
+                        "tracker_host",
+                        "private"
+                    ]
+                ]),
+            )
+            .await?;
+        let list: HashMap<String, TorrentStatus> = serde_json::from_value(result)
+            .map_err(|e| format!("Failed to parse torrents from Deluge API: {}", e))?;
+
+        let mut files: Vec<File> = vec![];
+        for (hash, item) in list {
+            files.push(File {
+                id: 0,
+                server_id: self.ids.id_for(&hash),
+                added_date: item.time_added as i64,
+                // deluge reports the progress as a percentage
+                finish_date: if item.is_finished || item.progress >= 100.0 {
+                    if item.completed_time > 0.0 {
+                        Some(item.completed_time as i64)
+                    } else {
+                        Some(
+                            std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs() as i64,
+                        )
+                    }
+                } else {
+                    None
+                },
+                finish_date_estimated: (item.is_finished || item.progress >= 100.0)
+                    && item.completed_time <= 0.0,
+                // deluge reports -1 when nothing was downloaded yet
+                upload_ratio: item.ratio.max(0.0),
+                seed_ratio_limit: item.stop_ratio,
+                seed_ratio_mode: if item.stop_at_ratio {
+                    SEED_RATIO_MODE_SINGLE
+                } else {
+                    SEED_RATIO_MODE_UNLIMITED
+                },
+                hash: hash.to_lowercase(),
+                name: item.name,
+                size: item.total_size,
+                download_dir: item.save_path,
+                labels: if item.label.is_empty() {
+                    vec![]
+                } else {
+                    vec![item.label]
+                },
+                trackers: if item.tracker_host.is_empty() {
+                    vec![]
+                } else {
+                    vec![item.tracker_host]
+                },
+                private: item.private,
+                ..Default::default()
+            });
+        }
+
+        Ok(files)
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        let result = self
+            .call("core.remove_torrents", json!([hashes, delete_data]))
+            .await?;
+
+        // deluge returns the list of torrents that failed to be removed
+        match result.as_array() {
+            Some(errors) if !errors.is_empty() => {
+                Err(format!("Failed to delete files: {:?}", errors))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn free_space(&mut self, path: &str) -> Result<i64, String> {
+        self.call("core.get_free_space", json!([path]))
+            .await?
+            .as_i64()
+            .ok_or_else(|| "Invalid free space in Deluge API response".to_string())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            completion_date: true,
+            keep_data: true,
+            free_space: true,
+            move_data: false,
+            stop: false,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use url::Url;
+
+use crate::logic::api::{Capabilities, HashIds, TorrentClient, parse_url, tracker_host};
+use crate::logic::database::models::{
+    File, SEED_RATIO_MODE_GLOBAL, SEED_RATIO_MODE_SINGLE, SEED_RATIO_MODE_UNLIMITED,
+};
+
+#[derive(Deserialize, Debug)]
+struct TorrentInfo {
+    hash: String,
+    added_on: i64,
+    completion_on: i64,
+    progress: f64,
+    #[serde(default)]
+    ratio: f64,
+    /// -2 uses the global limit and -1 disables the limit.
+    #[serde(default = "global_ratio_limit")]
+    ratio_limit: f64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    total_size: i64,
+    #[serde(default)]
+    save_path: String,
+    #[serde(default)]
+    category: String,
+    /// Comma separated list of tags.
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    tracker: String,
+    /// Only reported since qBittorrent 5.0.
+    #[serde(default, alias = "is_private")]
+    private: bool,
+}
+
+fn global_ratio_limit() -> f64 {
+    -2.0
+}
+
+pub struct QBittorrentApi {
+    client: reqwest::Client,
+    base_url: Url,
+    username: String,
+    password: String,
+    session_cookie: Option<String>,
+    ids: HashIds,
+}
+
+impl QBittorrentApi {
+    pub fn new(username: String, password: String, api_url: &str) -> Result<Self, String> {
+        let mut base_url = parse_url(api_url)?;
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(format!("{}/", base_url.path()).as_str());
+        }
+
+        Ok(QBittorrentApi {
+            client: reqwest::Client::new(),
+            base_url,
+            username,
+            password,
+            session_cookie: None,
+            ids: HashIds::default(),
+        })
+    }
+
+    fn endpoint(&self, path: &str) -> Url {
+        self.base_url.join(path).expect("Invalid API path")
+    }
+
+    async fn login(&mut self) -> Result<(), String> {
+        let res = self
+            .client
+            .post(self.endpoint("api/v2/auth/login"))
+            .header("Referer", self.base_url.as_str())
+            .form(&[
+                ("username", self.username.as_str()),
+                ("password", self.password.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Failed to login into qBittorrent API: {}", e))?;
+
+        let cookie = res
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.split(';').next())
+            .find(|value| value.starts_with("SID="))
+            .map(|value| value.to_string());
+        let body = res.text().await.unwrap_or_default();
+
+        match cookie {
+            Some(cookie) if body.trim() != "Fails." => {
+                self.session_cookie = Some(cookie);
+                Ok(())
+            }
+            _ => Err("Failed to login into qBittorrent API: invalid credentials".to_string()),
+        }
+    }
+
+    /// Send a request with the session cookie, login again once if the session expired.
+    async fn send(
+        &mut self,
+        path: &str,
+        form: Option<&[(&str, &str)]>,
+    ) -> Result<reqwest::Response, String> {
+        for _ in 0..2 {
+            if self.session_cookie.is_none() {
+                self.login().await?;
+            }
+
+            let request = match form {
+                Some(form) => self.client.post(self.endpoint(path)).form(form),
+                None => self.client.get(self.endpoint(path)),
+            };
+            let res = request
+                .header("Referer", self.base_url.as_str())
+                .header("Cookie", self.session_cookie.clone().unwrap_or_default())
+                .send()
+                .await
+                .map_err(|e| format!("Failed to call qBittorrent API: {}", e))?;
+
+            if res.status() == StatusCode::FORBIDDEN {
+                self.session_cookie = None;
+                continue;
+            }
+            if !res.status().is_success() {
+                return Err(format!("qBittorrent API returned status {}", res.status()));
+            }
+            return Ok(res);
+        }
+
+        Err("qBittorrent API rejected the session".to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for QBittorrentApi {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        let list: Vec<TorrentInfo> = self
+            .send("api/v2/torrents/info", None)
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse torrents from qBittorrent API: {}", e))?;
+
+        let mut files: Vec<File> = vec![];
+        for item in list {
+            files.push(File {
+                id: 0,
+                server_id: self.ids.id_for(&item.hash),
+                added_date: item.added_on,
+                finish_date: if item.progress >= 1.0 {
+                    if item.completion_on > 0 {
+                        Some(item.completion_on)
+                    } else {
+                        Some(
+                            std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs() as i64,
+                        )
+                    }
+                } else {
+                    None
+                },
+                finish_date_estimated: item.progress >= 1.0 && item.completion_on <= 0,
+                upload_ratio: item.ratio,
+                seed_ratio_limit: item.ratio_limit.max(0.0),
+                seed_ratio_mode: match item.ratio_limit {
+                    limit if limit >= 0.0 => SEED_RATIO_MODE_SINGLE,
+                    -1.0 => SEED_RATIO_MODE_UNLIMITED,
+                    _ => SEED_RATIO_MODE_GLOBAL,
+                },
+                hash: item.hash.to_lowercase(),
+                name: item.name,
+                size: item.total_size,
+                download_dir: item.save_path,
+                // categories and tags are both exposed as labels
+                labels: std::iter::once(item.category.as_str())
+                    .chain(item.tags.split(','))
+                    .map(|label| label.trim().to_string())
+                    .filter(|label| !label.is_empty())
+                    .collect(),
+                trackers: tracker_host(&item.tracker).into_iter().collect(),
+                private: item.private,
+                ..Default::default()
+            });
+        }
+
+        Ok(files)
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        let hashes = hashes.join("|");
+        self.send(
+            "api/v2/torrents/delete",
+            Some(&[
+                ("hashes", hashes.as_str()),
+                ("deleteFiles", if delete_data { "true" } else { "false" }),
+            ]),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            completion_date: true,
+            keep_data: true,
+            free_space: false,
+            move_data: false,
+            stop: false,
+        }
+    }
+}
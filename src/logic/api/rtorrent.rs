@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::Url;
+
+use crate::logic::api::rtorrent::xmlrpc::Value;
+use crate::logic::api::{Capabilities, HashIds, TorrentClient, parse_url};
+use crate::logic::database::models::File;
+
+mod xmlrpc;
+
+enum Transport {
+    Http(Url),
+    ScgiTcp(String),
+    ScgiUnix(PathBuf),
+}
+
+pub struct RTorrentApi {
+    client: reqwest::Client,
+    transport: Transport,
+    username: String,
+    password: String,
+    ids: HashIds,
+}
+
+impl RTorrentApi {
+    /// Create a backend for the given endpoint, `http(s)://` urls are used as XML-RPC endpoints,
+    /// `scgi://host:port` and `scgi:///path/to/socket` connect directly to the rTorrent SCGI socket.
+    pub fn new(username: String, password: String, api_url: &str) -> Result<Self, String> {
+        let url = parse_url(api_url)?;
+        let transport = match url.scheme() {
+            "scgi" if url.host_str().is_some() => Transport::ScgiTcp(format!(
+                "{}:{}",
+                url.host_str().unwrap(),
+                url.port().unwrap_or(5000)
+            )),
+            "scgi" | "unix" => Transport::ScgiUnix(PathBuf::from(url.path())),
+            _ => Transport::Http(url),
+        };
+
+        Ok(RTorrentApi {
+            client: reqwest::Client::new(),
+            transport,
+            username,
+            password,
+            ids: HashIds::default(),
+        })
+    }
+
+    async fn call(&self, method: &str, params: &[Value]) -> Result<Value, String> {
+        let body = xmlrpc::encode_call(method, params);
+        let response = match &self.transport {
+            Transport::Http(url) => {
+                let mut request = self
+                    .client
+                    .post(url.clone())
+                    .header("Content-Type", "text/xml")
+                    .body(body);
+                if !self.username.is_empty() {
+                    request = request.basic_auth(&self.username, Some(&self.password));
+                }
+                let res = request
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to call rTorrent API: {}", e))?;
+                if !res.status().is_success() {
+                    return Err(format!("rTorrent API returned status {}", res.status()));
+                }
+                res.text()
+                    .await
+                    .map_err(|e| format!("Failed to read rTorrent API response: {}", e))?
+            }
+            Transport::ScgiTcp(address) => {
+                let stream = tokio::net::TcpStream::connect(address)
+                    .await
+                    .map_err(|e| format!("Failed to connect to rTorrent SCGI socket: {}", e))?;
+                Self::scgi_request(stream, body).await?
+            }
+            Transport::ScgiUnix(path) => {
+                let stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(|e| format!("Failed to connect to rTorrent SCGI socket: {}", e))?;
+                Self::scgi_request(stream, body).await?
+            }
+        };
+
+        xmlrpc::decode_response(&response)
+    }
+
+    async fn scgi_request<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        body: String,
+    ) -> Result<String, String> {
+        let headers = format!(
+            "CONTENT_LENGTH\0{}\0SCGI\x001\0REQUEST_METHOD\0POST\0REQUEST_URI\0/RPC2\0",
+            body.len()
+        );
+        let request = format!("{}:{},{}", headers.len(), headers, body);
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to rTorrent SCGI socket: {}", e))?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .map_err(|e| format!("Failed to read from rTorrent SCGI socket: {}", e))?;
+
+        // skip the CGI headers sent before the XML body
+        match response.find("\r\n\r\n") {
+            Some(index) => Ok(response[index + 4..].to_string()),
+            None => Ok(response),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for RTorrentApi {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        let list = self
+            .call(
+                "d.multicall2",
+                &[
+                    Value::from(""),
+                    Value::from("main"),
+                    Value::from("d.hash="),
+                    // d.creation_date is when the .torrent file was made, not when it was added
+                    Value::from("d.load_date="),
+                    Value::from("d.timestamp.started="),
+                    Value::from("d.timestamp.finished="),
+                    Value::from("d.complete="),
+                    Value::from("d.name="),
+                    Value::from("d.size_bytes="),
+                    Value::from("d.directory="),
+                    // label set by ruTorrent
+                    Value::from("d.custom1="),
+                    Value::from("d.is_private="),
+                    Value::from("d.ratio="),
+                ],
+            )
+            .await?;
+
+        let mut files: Vec<File> = vec![];
+        for item in list.as_array().ok_or("Invalid d.multicall2 response")? {
+            let fields = item.as_array().ok_or("Invalid d.multicall2 response")?;
+            if fields.len() < 5 {
+                return Err("Invalid d.multicall2 response".to_string());
+            }
+            let hash = fields[0].as_str().ok_or("Missing torrent hash")?;
+            // torrents without load date fall back to the date they were first started
+            let added_date = match fields[1].as_i64().unwrap_or(0) {
+                0 => fields[2].as_i64().unwrap_or(0),
+                date => date,
+            };
+            let finished_date = fields[3].as_i64().unwrap_or(0);
+            let complete = fields[4].as_i64().unwrap_or(0) == 1;
+            let field_str = |index: usize| {
+                fields
+                    .get(index)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let label = field_str(8);
+
+            files.push(File {
+                id: 0,
+                server_id: self.ids.id_for(hash),
+                added_date,
+                finish_date: if finished_date > 0 {
+                    Some(finished_date)
+                } else if complete {
+                    Some(
+                        std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs() as i64,
+                    )
+                } else {
+                    None
+                },
+                finish_date_estimated: finished_date <= 0 && complete,
+                hash: hash.to_lowercase(),
+                name: field_str(5),
+                size: fields.get(6).and_then(|value| value.as_i64()).unwrap_or(0),
+                download_dir: field_str(7),
+                labels: if label.is_empty() {
+                    vec![]
+                } else {
+                    vec![label]
+                },
+                private: fields.get(9).and_then(|value| value.as_i64()).unwrap_or(0) == 1,
+                // rtorrent reports the ratio multiplied by 1000
+                upload_ratio: fields.get(10).and_then(|value| value.as_i64()).unwrap_or(0) as f64
+                    / 1000.0,
+                ..Default::default()
+            });
+        }
+
+        Ok(files)
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        for hash in hashes {
+            // rtorrent reports the hashes in uppercase
+            let hash = hash.to_uppercase();
+            let data_path = self
+                .call("d.data_path", &[Value::from(hash.as_str())])
+                .await?;
+            self.call("d.erase", &[Value::from(hash.as_str())]).await?;
+
+            // rtorrent does not remove the data when erasing a torrent
+            if delete_data
+                && let Some(path) = data_path.as_str()
+                && !path.is_empty()
+            {
+                self.call(
+                    "execute.throw",
+                    &[
+                        Value::from(""),
+                        Value::from("rm"),
+                        Value::from("-rf"),
+                        Value::from("--"),
+                        Value::from(path),
+                    ],
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            completion_date: true,
+            keep_data: true,
+            free_space: false,
+            move_data: false,
+            stop: false,
+        }
+    }
+}
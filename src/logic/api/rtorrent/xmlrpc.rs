@@ -0,0 +1,314 @@
+/// Minimal XML-RPC support for the subset of types used by rTorrent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Double(f64),
+    String(String),
+    Array(Vec<Value>),
+    Struct(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            Value::Bool(value) => Some(*value as i64),
+            Value::String(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, out: &mut String) {
+        out.push_str("<value>");
+        match self {
+            Value::Int(value) => out.push_str(format!("<i8>{}</i8>", value).as_str()),
+            Value::Bool(value) => {
+                out.push_str(format!("<boolean>{}</boolean>", *value as i32).as_str())
+            }
+            Value::Double(value) => out.push_str(format!("<double>{}</double>", value).as_str()),
+            Value::String(value) => {
+                out.push_str(format!("<string>{}</string>", escape(value)).as_str())
+            }
+            Value::Array(values) => {
+                out.push_str("<array><data>");
+                for value in values {
+                    value.encode(out);
+                }
+                out.push_str("</data></array>");
+            }
+            Value::Struct(members) => {
+                out.push_str("<struct>");
+                for (name, value) in members {
+                    out.push_str(format!("<member><name>{}</name>", escape(name)).as_str());
+                    value.encode(out);
+                    out.push_str("</member>");
+                }
+                out.push_str("</struct>");
+            }
+        }
+        out.push_str("</value>");
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn encode_call(method: &str, params: &[Value]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\"?><methodCall>");
+    out.push_str(format!("<methodName>{}</methodName><params>", escape(method)).as_str());
+    for param in params {
+        out.push_str("<param>");
+        param.encode(&mut out);
+        out.push_str("</param>");
+    }
+    out.push_str("</params></methodCall>");
+    out
+}
+
+/// Parse a method response, faults are returned as errors.
+pub fn decode_response(body: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        input: body,
+        pos: 0,
+    };
+    if let Some(start) = body.find("<methodResponse>") {
+        parser.pos = start;
+    } else {
+        return Err("Invalid XML-RPC response".to_string());
+    }
+    parser.expect("methodResponse")?;
+
+    match parser.next_tag()?.as_str() {
+        "fault" => {
+            let fault = parser.value()?;
+            let message = match &fault {
+                Value::Struct(members) => members
+                    .iter()
+                    .find(|(name, _)| name == "faultString")
+                    .and_then(|(_, value)| value.as_str().map(|v| v.to_string())),
+                _ => None,
+            };
+            Err(format!(
+                "XML-RPC fault: {}",
+                message.unwrap_or_else(|| format!("{:?}", fault))
+            ))
+        }
+        "params" => {
+            parser.expect("param")?;
+            parser.value()
+        }
+        tag => Err(format!("Unexpected XML-RPC tag: {}", tag)),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.input[self.pos..].chars().next() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Read the next tag, including the leading `/` of closing tags and the trailing `/` of empty ones.
+    fn next_tag(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        if !self.input[self.pos..].starts_with('<') {
+            return Err(format!("Expected XML tag at position {}", self.pos));
+        }
+        let end = self.input[self.pos..]
+            .find('>')
+            .ok_or("Unterminated XML tag")?;
+        let tag = self.input[self.pos + 1..self.pos + end].trim();
+        self.pos += end + 1;
+        match tag.strip_suffix('/') {
+            Some(name) => Ok(format!("{}/", name.trim())),
+            None => Ok(tag.to_string()),
+        }
+    }
+
+    fn expect(&mut self, name: &str) -> Result<(), String> {
+        let tag = self.next_tag()?;
+        if tag != name {
+            return Err(format!("Expected <{}> but found <{}>", name, tag));
+        }
+        Ok(())
+    }
+
+    fn text(&mut self) -> String {
+        let end = self.input[self.pos..]
+            .find('<')
+            .unwrap_or(self.input.len() - self.pos);
+        let text = unescape(&self.input[self.pos..self.pos + end]);
+        self.pos += end;
+        text
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.expect("value")?;
+
+        // untyped values are strings
+        let start = self.pos;
+        let text = self.text();
+        if self.input[self.pos..].starts_with("</value>") {
+            self.pos += "</value>".len();
+            return Ok(Value::String(text));
+        }
+        self.pos = start;
+
+        let tag = self.next_tag()?;
+        let value = match tag.as_str() {
+            "i4" | "i8" | "int" => {
+                let text = self.text();
+                Value::Int(
+                    text.trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid XML-RPC integer: {}", text))?,
+                )
+            }
+            "boolean" => Value::Bool(self.text().trim() == "1"),
+            "double" => {
+                let text = self.text();
+                Value::Double(
+                    text.trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid XML-RPC double: {}", text))?,
+                )
+            }
+            "string" => Value::String(self.text()),
+            "string/" => return self.close_value(Value::String(String::new())),
+            "nil/" => return self.close_value(Value::String(String::new())),
+            "array" => {
+                self.expect("data")?;
+                let mut values = vec![];
+                loop {
+                    let start = self.pos;
+                    if self.next_tag()? == "/data" {
+                        break;
+                    }
+                    self.pos = start;
+                    values.push(self.value()?);
+                }
+                Value::Array(values)
+            }
+            "struct" => {
+                let mut members = vec![];
+                loop {
+                    match self.next_tag()?.as_str() {
+                        "/struct" => break,
+                        "member" => {}
+                        tag => return Err(format!("Unexpected XML-RPC tag: {}", tag)),
+                    }
+                    self.expect("name")?;
+                    let name = self.text();
+                    self.expect("/name")?;
+                    members.push((name, self.value()?));
+                    self.expect("/member")?;
+                }
+                return self.close_value(Value::Struct(members));
+            }
+            tag => return Err(format!("Unsupported XML-RPC type: {}", tag)),
+        };
+
+        self.expect(format!("/{}", tag).as_str())?;
+        self.close_value(value)
+    }
+
+    fn close_value(&mut self, value: Value) -> Result<Value, String> {
+        self.expect("/value")?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_call() {
+        let body = encode_call(
+            "d.multicall2",
+            &[Value::from(""), Value::from("main"), Value::Int(5)],
+        );
+        assert_eq!(
+            body,
+            "<?xml version=\"1.0\"?><methodCall><methodName>d.multicall2</methodName><params>\
+            <param><value><string></string></value></param>\
+            <param><value><string>main</string></value></param>\
+            <param><value><i8>5</i8></value></param></params></methodCall>"
+        );
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let body = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+            <methodResponse><params><param><value><array><data>
+              <value><array><data>
+                <value><string>ABC</string></value>
+                <value><i8>1763580763</i8></value>
+                <value>untyped &amp; text</value>
+                <value><string/></value>
+              </data></array></value>
+            </data></array></value></param></params></methodResponse>";
+        let value = decode_response(body).expect("Failed to decode response");
+        assert_eq!(
+            value,
+            Value::Array(vec![Value::Array(vec![
+                Value::String("ABC".to_string()),
+                Value::Int(1763580763),
+                Value::String("untyped & text".to_string()),
+                Value::String(String::new()),
+            ])])
+        );
+    }
+
+    #[test]
+    fn test_decode_fault() {
+        let body = "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>
+            <member><name>faultCode</name><value><i4>-506</i4></value></member>
+            <member><name>faultString</name><value><string>Method 'd.nope' not defined</string></value></member>
+            </struct></value></fault></methodResponse>";
+        let error = decode_response(body).expect_err("Fault should be an error");
+        assert_eq!(error, "XML-RPC fault: Method 'd.nope' not defined");
+    }
+}
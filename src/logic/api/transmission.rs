@@ -0,0 +1,197 @@
+use transmission_rpc::TransClient;
+use transmission_rpc::types::{BasicAuth, Id, TorrentAction, TorrentGetField};
+
+use crate::logic::api::{Capabilities, TorrentClient, parse_url, tracker_host};
+use crate::logic::database::models::File;
+
+pub struct TransmissionApi {
+    client: Option<TransClient>,
+}
+
+impl TransmissionApi {
+    pub fn new(username: String, password: String, api_url: &str) -> Result<Self, String> {
+        Ok(TransmissionApi {
+            client: Some(TransClient::with_auth(
+                parse_url(api_url)?,
+                BasicAuth {
+                    user: username,
+                    password,
+                },
+            )),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for TransmissionApi {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        let list = self
+            .client
+            .as_mut()
+            .unwrap()
+            .torrent_get(
+                Some(vec![
+                    TorrentGetField::Id,
+                    TorrentGetField::HashString,
+                    TorrentGetField::AddedDate,
+                    TorrentGetField::IsFinished,
+                    TorrentGetField::PercentDone,
+                    TorrentGetField::Name,
+                    TorrentGetField::TotalSize,
+                    TorrentGetField::DownloadDir,
+                    TorrentGetField::Labels,
+                    TorrentGetField::Trackers,
+                    TorrentGetField::IsPrivate,
+                    TorrentGetField::UploadRatio,
+                    TorrentGetField::SeedRatioLimit,
+                    TorrentGetField::SeedRatioMode,
+                    TorrentGetField::DoneDate,
+                    TorrentGetField::SecondsSeeding,
+                    TorrentGetField::ActivityDate,
+                ]),
+                None,
+            )
+            .await
+            .map_err(|e| format!("Failed to fetch torrents from Transmission API: {}", e))?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut files: Vec<File> = vec![];
+        for item in list.arguments.torrents {
+            // transmission reports 0 for the dates that are not known
+            let done_date = item
+                .done_date
+                .map(|date| date.timestamp())
+                .filter(|date| *date > 0);
+            let activity_date = item
+                .activity_date
+                .map(|date| date.timestamp())
+                .filter(|date| *date > 0);
+            let seconds_seeding = item.seconds_seeding.unwrap_or_default();
+            let finished = item.is_finished.expect("Missing isFinished value")
+                || item.percent_done.expect("Missing percentDone") >= 1.0;
+
+            // torrents added with their data already downloaded have no done date, their seeding
+            // time leaves out the pauses and their last activity is the estimate without one, so
+            // both are replaced by the done date once the client reports it
+            let (finish_date, finish_date_estimated) = match done_date {
+                _ if !finished => (None, false),
+                Some(date) => (Some(date), false),
+                None if seconds_seeding > 0 => (Some(now - seconds_seeding), true),
+                None => (Some(activity_date.unwrap_or(now)), true),
+            };
+
+            files.push(File {
+                id: 0,
+                server_id: item.id.expect("Missing torrent ID") as i32,
+                added_date: item.added_date.expect("Missing addedDate").timestamp(),
+                finish_date,
+                finish_date_estimated,
+                seconds_seeding,
+                activity_date,
+                // transmission reports negative ratios when nothing was downloaded yet
+                upload_ratio: item.upload_ratio.unwrap_or_default().max(0.0) as f64,
+                seed_ratio_limit: item.seed_ratio_limit.unwrap_or_default() as f64,
+                seed_ratio_mode: item.seed_ratio_mode.map(|mode| mode as i32).unwrap_or(0),
+                hash: item.hash_string.unwrap_or_default().to_lowercase(),
+                name: item.name.unwrap_or_default(),
+                size: item.total_size.unwrap_or_default(),
+                download_dir: item.download_dir.unwrap_or_default(),
+                labels: item.labels.unwrap_or_default(),
+                trackers: item
+                    .trackers
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|tracker| tracker_host(&tracker.announce))
+                    .collect(),
+                private: item.is_private.unwrap_or_default(),
+                ..Default::default()
+            });
+        }
+
+        Ok(files)
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        let res = self
+            .client
+            .as_mut()
+            .unwrap()
+            .torrent_remove(
+                hashes.iter().map(|hash| Id::Hash(hash.clone())).collect(),
+                delete_data,
+            )
+            .await
+            .map_err(|e| format!("Failed to delete files from Transmission API: {}", e))?;
+
+        if res.result != "success" {
+            return Err(format!("Failed to delete files: {}", res.result));
+        }
+        Ok(())
+    }
+
+    async fn move_file(&mut self, hashes: &[String], location: &str) -> Result<(), String> {
+        let res = self
+            .client
+            .as_mut()
+            .unwrap()
+            .torrent_set_location(
+                hashes.iter().map(|hash| Id::Hash(hash.clone())).collect(),
+                location.to_string(),
+                Some(true),
+            )
+            .await
+            .map_err(|e| format!("Failed to move files with Transmission API: {}", e))?;
+
+        if res.result != "success" {
+            return Err(format!("Failed to move files: {}", res.result));
+        }
+        Ok(())
+    }
+
+    async fn stop_file(&mut self, hashes: &[String]) -> Result<(), String> {
+        let res = self
+            .client
+            .as_mut()
+            .unwrap()
+            .torrent_action(
+                TorrentAction::Stop,
+                hashes.iter().map(|hash| Id::Hash(hash.clone())).collect(),
+            )
+            .await
+            .map_err(|e| format!("Failed to stop files with Transmission API: {}", e))?;
+
+        if res.result != "success" {
+            return Err(format!("Failed to stop files: {}", res.result));
+        }
+        Ok(())
+    }
+
+    async fn free_space(&mut self, path: &str) -> Result<i64, String> {
+        let res = self
+            .client
+            .as_mut()
+            .unwrap()
+            .free_space(path.to_string())
+            .await
+            .map_err(|e| format!("Failed to get free space from Transmission API: {}", e))?;
+
+        if res.result != "success" {
+            return Err(format!("Failed to get free space: {}", res.result));
+        }
+        Ok(res.arguments.size_bytes)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            completion_date: true,
+            keep_data: true,
+            free_space: true,
+            move_data: true,
+            stop: true,
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::logic::database::models::File;
+use crate::logic::database::models::{File, HistoryEntry, PlanEntry, Protection, TrashEntry};
+use crate::logic::history::HistoryFilter;
 use rusqlite::Connection;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -41,6 +42,21 @@ impl Database {
         std::mem::drop(self.connection.clone());
     }
 
+    /// Wait for the running queries and close the database file, so that everything is on disk
+    /// before the service exits. The database can be connected again.
+    pub async fn close(&self) -> Result<(), String> {
+        if self.in_memory {
+            return Ok(());
+        }
+        let mut connection = self.connection.lock().await;
+        let file = std::mem::replace(
+            &mut *connection,
+            Connection::open_in_memory().map_err(|e| e.to_string())?,
+        );
+        file.close()
+            .map_err(|(_, e)| format!("Failed to close the database: {}", e))
+    }
+
     pub async fn create_database(&self) {
         let table_count = {
             let connection = self.connection.lock().await;
@@ -119,54 +135,109 @@ impl Database {
     }
 
     pub async fn create_or_update_file(&self, file: File) -> i32 {
-        let existing_file = self.get_file_by_server_id(file.server_id).await;
-        if existing_file.is_none() {
-            println!("Inserting new file: {:?}", file);
-            self.connection
-                .lock()
+        let existing_file = if file.hash.is_empty() {
+            self.get_file_by_server_id(&file.instance, file.server_id)
                 .await
-                .execute(
-                    "INSERT INTO file (serverId, addedDate, finishDate) VALUES (?1, ?2, ?3);",
-                    (file.server_id, file.added_date, file.finish_date),
-                )
-                .expect("Failed to insert file into database");
-            let new_file = self.get_file_by_server_id(file.server_id).await;
-            new_file
-                .expect("Failed to retrieve newly inserted file")
-                .id
         } else {
-            let finish_date = if existing_file.clone().unwrap().finish_date.is_some() {
-                existing_file.clone().unwrap().finish_date
-            } else {
-                file.finish_date
-            };
+            match self.get_file_by_hash(&file.instance, &file.hash).await {
+                Some(existing_file) => Some(existing_file),
+                // files tracked before the hashes were stored are matched once by server id
+                None => self
+                    .get_file_by_server_id(&file.instance, file.server_id)
+                    .await
+                    .filter(|existing_file| existing_file.hash.is_empty()),
+            }
+        };
+        // empty hashes are stored as null so they do not collide
+        let hash = Some(file.hash.as_str()).filter(|hash| !hash.is_empty());
+        let trackers = serde_json::to_string(&file.trackers).unwrap();
+        let labels = serde_json::to_string(&file.labels).unwrap();
+        if let Some(existing_file) = existing_file {
+            // the client reassigns its ids when it restarts
+            if existing_file.server_id != file.server_id {
+                println!(
+                    "[{}] Torrent {} changed server id from {} to {}",
+                    file.instance, file, existing_file.server_id, file.server_id
+                );
+            }
+
+            // keep the first finish date seen unless the client now reports the real one, or an
+            // earlier estimate since the torrent was finished by every estimated date
+            let (finish_date, finish_date_estimated) =
+                match (existing_file.finish_date, file.finish_date) {
+                    (Some(existing), _) if !existing_file.finish_date_estimated => {
+                        (Some(existing), false)
+                    }
+                    (Some(existing), Some(date)) if file.finish_date_estimated => {
+                        (Some(existing.min(date)), true)
+                    }
+                    (Some(existing), None) => (Some(existing), true),
+                    _ => (file.finish_date, file.finish_date_estimated),
+                };
             self.connection
                 .lock()
                 .await
                 .execute(
-                    "UPDATE file SET addedDate = ?1, finishDate = ?2 WHERE serverId = ?3;",
-                    (file.added_date, finish_date, file.server_id),
+                    "UPDATE file SET addedDate = ?1, finishDate = ?2, uploadRatio = ?3, seedRatioLimit = ?4, seedRatioMode = ?5, secondsSeeding = ?6, activityDate = ?7, finishDateEstimated = ?8, serverId = ?9, hash = ?10, name = ?11, size = ?12, downloadDir = ?13, trackers = ?14, labels = ?15 WHERE id = ?16;",
+                    (
+                        file.added_date,
+                        finish_date,
+                        file.upload_ratio,
+                        file.seed_ratio_limit,
+                        file.seed_ratio_mode,
+                        file.seconds_seeding,
+                        file.activity_date,
+                        finish_date_estimated,
+                        file.server_id,
+                        hash,
+                        file.name.as_str(),
+                        file.size,
+                        file.download_dir.as_str(),
+                        trackers.as_str(),
+                        labels.as_str(),
+                        existing_file.id,
+                    ),
                 )
                 .expect("Failed to update file in database");
-            existing_file.unwrap().id
+            existing_file.id
+        } else {
+            println!("[{}] Tracking new torrent {}", file.instance, file);
+            let connection = self.connection.lock().await;
+            connection
+                .execute(
+                    "INSERT INTO file (serverId, addedDate, finishDate, instance, uploadRatio, seedRatioLimit, seedRatioMode, secondsSeeding, activityDate, finishDateEstimated, hash, name, size, downloadDir, trackers, labels) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16);",
+                    (
+                        file.server_id,
+                        file.added_date,
+                        file.finish_date,
+                        file.instance.as_str(),
+                        file.upload_ratio,
+                        file.seed_ratio_limit,
+                        file.seed_ratio_mode,
+                        file.seconds_seeding,
+                        file.activity_date,
+                        file.finish_date_estimated,
+                        hash,
+                        file.name.as_str(),
+                        file.size,
+                        file.download_dir.as_str(),
+                        trackers.as_str(),
+                        labels.as_str(),
+                    ),
+                )
+                .expect("Failed to insert file into database");
+            connection.last_insert_rowid() as i32
         }
     }
 
-    pub async fn get_file_by_server_id(&self, server_id: i32) -> Option<File> {
+    pub async fn get_file_by_server_id(&self, instance: &str, server_id: i32) -> Option<File> {
         let result = self
             .connection
             .lock()
             .await
-            .prepare("SELECT * FROM file WHERE serverId = ?1;")
+            .prepare("SELECT * FROM file WHERE instance = ?1 AND serverId = ?2;")
             .unwrap()
-            .query_map([server_id], |row| {
-                Ok(File {
-                    id: row.get(0)?,
-                    server_id: row.get(1)?,
-                    added_date: row.get(2)?,
-                    finish_date: row.get(3)?,
-                })
-            })
+            .query_map((instance, server_id), file_from_row)
             .expect("Failed to query file table")
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
@@ -178,37 +249,300 @@ impl Database {
         }
     }
 
-    pub async fn remove_no_matching_files_ids(&self, ids: &Vec<i32>) {
+    pub async fn get_file_by_hash(&self, instance: &str, hash: &str) -> Option<File> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT * FROM file WHERE instance = ?1 AND hash = ?2;")
+            .unwrap()
+            .query_map((instance, hash), file_from_row)
+            .expect("Failed to query file table")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+            .into_iter()
+            .next()
+    }
+
+    /// Remove the files of the instance that are not in the given list of ids.
+    pub async fn remove_no_matching_files_ids(&self, instance: &str, ids: &[i32]) {
         let ids_placeholders: Vec<String> =
             ids.iter().map(|v| format!("{}", v).to_string()).collect();
         let sql = format!(
-            "DELETE FROM file WHERE id NOT IN ({});",
+            "DELETE FROM file WHERE instance = ?1 AND id NOT IN ({});",
             ids_placeholders.join(", ")
         );
 
         self.connection
             .lock()
             .await
-            .execute(sql.as_str(), [])
+            .execute(sql.as_str(), [instance])
             .expect("Failed to delete non-matching files");
     }
 
-    pub async fn list_of_file_ids(&self) -> Vec<File> {
+    /// Record that the service stopped the file.
+    pub async fn set_file_stopped(&self, id: i32, stopped_date: i64) {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "UPDATE file SET stoppedDate = ?1 WHERE id = ?2;",
+                (stopped_date, id),
+            )
+            .expect("Failed to update file in database");
+    }
+
+    pub async fn list_of_file_ids(&self, instance: &str) -> Vec<File> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT * FROM file WHERE instance = ?1;")
+            .unwrap()
+            .query_map([instance], file_from_row)
+            .expect("Failed to query file table")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    /// Replace the stored plan of the instance.
+    pub async fn replace_plan(&self, instance: &str, entries: &[PlanEntry]) {
+        let mut connection = self.connection.lock().await;
+        let transaction = connection
+            .transaction()
+            .expect("Failed to start plan transaction");
+        transaction
+            .execute("DELETE FROM plan WHERE instance = ?1;", [instance])
+            .expect("Failed to delete old plan");
+        for entry in entries {
+            transaction
+                .execute(
+                    "INSERT INTO plan (instance, serverId, reason, dueDate, plannedDate, rule, mode, name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
+                    (
+                        instance,
+                        entry.server_id,
+                        entry.reason.as_str(),
+                        entry.due_date,
+                        entry.planned_date,
+                        entry.rule.as_deref(),
+                        entry.mode.as_str(),
+                        entry.name.as_str(),
+                    ),
+                )
+                .expect("Failed to insert plan entry");
+        }
+        transaction.commit().expect("Failed to commit plan");
+    }
+
+    pub async fn list_plan(&self, instance: &str) -> Vec<PlanEntry> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT * FROM plan WHERE instance = ?1 ORDER BY dueDate ASC;")
+            .unwrap()
+            .query_map([instance], |row| {
+                Ok(PlanEntry {
+                    id: row.get(0)?,
+                    instance: row.get(1)?,
+                    server_id: row.get(2)?,
+                    reason: row.get(3)?,
+                    due_date: row.get(4)?,
+                    planned_date: row.get(5)?,
+                    rule: row.get(6)?,
+                    mode: row.get(7)?,
+                    name: row.get(8)?,
+                })
+            })
+            .expect("Failed to query plan table")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    pub async fn add_trash_entry(&self, entry: &TrashEntry) {
         self.connection
             .lock()
             .await
-            .prepare("SELECT * FROM file;")
+            .execute(
+                "INSERT INTO trash (instance, name, path, size, trashedDate) VALUES (?1, ?2, ?3, ?4, ?5);",
+                (
+                    entry.instance.as_str(),
+                    entry.name.as_str(),
+                    entry.path.as_str(),
+                    entry.size,
+                    entry.trashed_date,
+                ),
+            )
+            .expect("Failed to insert trash entry");
+    }
+
+    /// List the trash entries of the instance, the oldest first.
+    pub async fn list_trash(&self, instance: &str) -> Vec<TrashEntry> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT * FROM trash WHERE instance = ?1 ORDER BY trashedDate ASC, id ASC;")
+            .unwrap()
+            .query_map([instance], |row| {
+                Ok(TrashEntry {
+                    id: row.get(0)?,
+                    instance: row.get(1)?,
+                    name: row.get(2)?,
+                    path: row.get(3)?,
+                    size: row.get(4)?,
+                    trashed_date: row.get(5)?,
+                })
+            })
+            .expect("Failed to query trash table")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    pub async fn remove_trash_entry(&self, id: i32) {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM trash WHERE id = ?1;", [id])
+            .expect("Failed to delete trash entry");
+    }
+
+    /// Append a removal to the deletion history, entries are never updated.
+    pub async fn add_history_entry(&self, entry: &HistoryEntry) {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO deletion_history (instance, hash, name, size, reason, rule, mode, deletedDate, result) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9);",
+                (
+                    entry.instance.as_str(),
+                    entry.hash.as_str(),
+                    entry.name.as_str(),
+                    entry.size,
+                    entry.reason.as_str(),
+                    entry.rule.as_deref(),
+                    entry.mode.as_str(),
+                    entry.deleted_date,
+                    entry.result.as_str(),
+                ),
+            )
+            .expect("Failed to insert deletion history entry");
+    }
+
+    /// List the deletion history of all the instances matching the filter, the oldest first.
+    pub async fn list_history(&self, filter: &HistoryFilter) -> Vec<HistoryEntry> {
+        self.connection
+            .lock()
+            .await
+            .prepare(
+                "SELECT * FROM deletion_history WHERE (?1 IS NULL OR deletedDate >= ?1) AND (?2 IS NULL OR deletedDate < ?2) AND (?3 IS NULL OR instr(lower(name), lower(?3)) > 0) ORDER BY deletedDate ASC, id ASC;",
+            )
+            .unwrap()
+            .query_map((filter.from, filter.to, filter.name.as_deref()), |row| {
+                Ok(HistoryEntry {
+                    id: row.get(0)?,
+                    instance: row.get(1)?,
+                    hash: row.get(2)?,
+                    name: row.get(3)?,
+                    size: row.get(4)?,
+                    reason: row.get(5)?,
+                    rule: row.get(6)?,
+                    mode: row.get(7)?,
+                    deleted_date: row.get(8)?,
+                    result: row.get(9)?,
+                })
+            })
+            .expect("Failed to query deletion_history table")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    /// Date the last email digest covered, if one was ever sent or scheduled.
+    pub async fn last_digest_date(&self) -> Option<i64> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT sentDate FROM digest WHERE id = 1;")
+            .unwrap()
+            .query_one([], |row| row.get(0))
+            .ok()
+    }
+
+    /// Keep the date the last email digest covered, replacing the previous one.
+    pub async fn set_last_digest_date(&self, date: i64) {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT OR REPLACE INTO digest (id, sentDate) VALUES (1, ?1);",
+                [date],
+            )
+            .expect("Failed to update digest date");
+    }
+
+    /// Add a protection and return its id.
+    pub async fn add_protection(&self, protection: &Protection) -> i32 {
+        let connection = self.connection.lock().await;
+        connection
+            .execute(
+                "INSERT INTO protected (kind, value, createdDate) VALUES (?1, ?2, ?3);",
+                (
+                    protection.kind.as_str(),
+                    protection.value.as_str(),
+                    protection.created_date,
+                ),
+            )
+            .expect("Failed to insert protection");
+        connection.last_insert_rowid() as i32
+    }
+
+    pub async fn list_protections(&self) -> Vec<Protection> {
+        self.connection
+            .lock()
+            .await
+            .prepare("SELECT * FROM protected ORDER BY id ASC;")
             .unwrap()
             .query_map([], |row| {
-                Ok(File {
+                Ok(Protection {
                     id: row.get(0)?,
-                    server_id: row.get(1)?,
-                    added_date: row.get(2)?,
-                    finish_date: row.get(3)?,
+                    kind: row.get(1)?,
+                    value: row.get(2)?,
+                    created_date: row.get(3)?,
                 })
             })
-            .expect("Failed to query file table")
+            .expect("Failed to query protected table")
             .collect::<Result<Vec<_>, _>>()
             .unwrap()
     }
+
+    /// Remove a protection, returns false if it does not exist.
+    pub async fn remove_protection(&self, id: i32) -> bool {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM protected WHERE id = ?1;", [id])
+            .expect("Failed to delete protection")
+            > 0
+    }
+}
+
+// columns are read by name, their order depends on the migrations applied
+fn file_from_row(row: &rusqlite::Row) -> rusqlite::Result<File> {
+    Ok(File {
+        id: row.get("id")?,
+        server_id: row.get("serverId")?,
+        hash: row.get::<_, Option<String>>("hash")?.unwrap_or_default(),
+        added_date: row.get("addedDate")?,
+        finish_date: row.get("finishDate")?,
+        instance: row.get("instance")?,
+        upload_ratio: row.get("uploadRatio")?,
+        seed_ratio_limit: row.get("seedRatioLimit")?,
+        seed_ratio_mode: row.get("seedRatioMode")?,
+        seconds_seeding: row.get("secondsSeeding")?,
+        activity_date: row.get("activityDate")?,
+        finish_date_estimated: row.get("finishDateEstimated")?,
+        stopped_date: row.get("stoppedDate")?,
+        name: row.get("name")?,
+        size: row.get("size")?,
+        download_dir: row.get("downloadDir")?,
+        trackers: serde_json::from_str(&row.get::<_, String>("trackers")?).unwrap_or_default(),
+        labels: serde_json::from_str(&row.get::<_, String>("labels")?).unwrap_or_default(),
+        ..Default::default()
+    })
 }
@@ -34,6 +34,341 @@ impl Migration for InitialMigration {
         "Initial migration".to_string()
     }
 }
+
+pub struct InstanceMigration {}
+
+#[async_trait::async_trait]
+impl Migration for InstanceMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding instance to file table...");
+        // sqlite can not change the unique constraint of a column, so the table is rebuilt
+        connection
+            .lock()
+            .await
+            .execute_batch(
+                "CREATE TABLE file_new ( id INTEGER PRIMARY KEY, serverId INTEGER NOT NULL, addedDate INTEGER NOT NULL, finishDate INTEGER, instance TEXT NOT NULL DEFAULT 'default', UNIQUE (instance, serverId) );
+                INSERT INTO file_new (id, serverId, addedDate, finishDate) SELECT id, serverId, addedDate, finishDate FROM file;
+                DROP TABLE file;
+                ALTER TABLE file_new RENAME TO file;",
+            )
+            .expect("Error adding instance to file table");
+    }
+
+    fn version(&self) -> u16 {
+        2
+    }
+
+    fn description(&self) -> String {
+        "Add instance to file table".to_string()
+    }
+}
+
+pub struct PlanMigration {}
+
+#[async_trait::async_trait]
+impl Migration for PlanMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Creating plan table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "CREATE TABLE plan ( id INTEGER PRIMARY KEY, instance TEXT NOT NULL, serverId INTEGER NOT NULL, reason TEXT NOT NULL, dueDate INTEGER NOT NULL, plannedDate INTEGER NOT NULL );",
+                [],
+            )
+            .expect("Error creating plan table");
+    }
+
+    fn version(&self) -> u16 {
+        3
+    }
+
+    fn description(&self) -> String {
+        "Add plan table".to_string()
+    }
+}
+
+pub struct PlanRuleMigration {}
+
+#[async_trait::async_trait]
+impl Migration for PlanRuleMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding rule to plan table...");
+        connection
+            .lock()
+            .await
+            .execute("ALTER TABLE plan ADD COLUMN rule TEXT;", [])
+            .expect("Error adding rule to plan table");
+    }
+
+    fn version(&self) -> u16 {
+        4
+    }
+
+    fn description(&self) -> String {
+        "Add rule to plan table".to_string()
+    }
+}
+
+pub struct RatioMigration {}
+
+#[async_trait::async_trait]
+impl Migration for RatioMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding ratio to file table...");
+        connection
+            .lock()
+            .await
+            .execute_batch(
+                "ALTER TABLE file ADD COLUMN uploadRatio REAL NOT NULL DEFAULT 0;
+                ALTER TABLE file ADD COLUMN seedRatioLimit REAL NOT NULL DEFAULT 0;
+                ALTER TABLE file ADD COLUMN seedRatioMode INTEGER NOT NULL DEFAULT 0;",
+            )
+            .expect("Error adding ratio to file table");
+    }
+
+    fn version(&self) -> u16 {
+        5
+    }
+
+    fn description(&self) -> String {
+        "Add ratio to file table".to_string()
+    }
+}
+
+pub struct SeedingTimesMigration {}
+
+#[async_trait::async_trait]
+impl Migration for SeedingTimesMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding seeding times to file table...");
+        // finish dates stored so far come from the scan clock, they are replaced by the ones
+        // reported by the client on the next scan
+        connection
+            .lock()
+            .await
+            .execute_batch(
+                "ALTER TABLE file ADD COLUMN secondsSeeding INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE file ADD COLUMN activityDate INTEGER;
+                ALTER TABLE file ADD COLUMN finishDateEstimated INTEGER NOT NULL DEFAULT 0;
+                UPDATE file SET finishDateEstimated = 1 WHERE finishDate IS NOT NULL;",
+            )
+            .expect("Error adding seeding times to file table");
+    }
+
+    fn version(&self) -> u16 {
+        6
+    }
+
+    fn description(&self) -> String {
+        "Add seeding times to file table".to_string()
+    }
+}
+
+pub struct PlanModeMigration {}
+
+#[async_trait::async_trait]
+impl Migration for PlanModeMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding removal mode to plan table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "ALTER TABLE plan ADD COLUMN mode TEXT NOT NULL DEFAULT 'delete_data';",
+                [],
+            )
+            .expect("Error adding removal mode to plan table");
+    }
+
+    fn version(&self) -> u16 {
+        7
+    }
+
+    fn description(&self) -> String {
+        "Add removal mode to plan table".to_string()
+    }
+}
+
+pub struct TrashMigration {}
+
+#[async_trait::async_trait]
+impl Migration for TrashMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Creating trash table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "CREATE TABLE trash ( id INTEGER PRIMARY KEY, instance TEXT NOT NULL, name TEXT NOT NULL, path TEXT NOT NULL, size INTEGER NOT NULL, trashedDate INTEGER NOT NULL );",
+                [],
+            )
+            .expect("Error creating trash table");
+    }
+
+    fn version(&self) -> u16 {
+        8
+    }
+
+    fn description(&self) -> String {
+        "Add trash table".to_string()
+    }
+}
+
+pub struct ProtectedMigration {}
+
+#[async_trait::async_trait]
+impl Migration for ProtectedMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Creating protected table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "CREATE TABLE protected ( id INTEGER PRIMARY KEY, kind TEXT NOT NULL, value TEXT NOT NULL, createdDate INTEGER NOT NULL );",
+                [],
+            )
+            .expect("Error creating protected table");
+    }
+
+    fn version(&self) -> u16 {
+        9
+    }
+
+    fn description(&self) -> String {
+        "Add protected table".to_string()
+    }
+}
+
+pub struct StoppedDateMigration {}
+
+#[async_trait::async_trait]
+impl Migration for StoppedDateMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding stopped date to file table...");
+        connection
+            .lock()
+            .await
+            .execute("ALTER TABLE file ADD COLUMN stoppedDate INTEGER;", [])
+            .expect("Error adding stopped date to file table");
+    }
+
+    fn version(&self) -> u16 {
+        10
+    }
+
+    fn description(&self) -> String {
+        "Add stopped date to file table".to_string()
+    }
+}
+
+pub struct HashMigration {}
+
+#[async_trait::async_trait]
+impl Migration for HashMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding hash to file table...");
+        // the server id is not unique anymore, torrents are identified by their hash
+        connection
+            .lock()
+            .await
+            .execute_batch(
+                "CREATE TABLE file_new ( id INTEGER PRIMARY KEY, serverId INTEGER NOT NULL, addedDate INTEGER NOT NULL, finishDate INTEGER, instance TEXT NOT NULL DEFAULT 'default', uploadRatio REAL NOT NULL DEFAULT 0, seedRatioLimit REAL NOT NULL DEFAULT 0, seedRatioMode INTEGER NOT NULL DEFAULT 0, secondsSeeding INTEGER NOT NULL DEFAULT 0, activityDate INTEGER, finishDateEstimated INTEGER NOT NULL DEFAULT 0, stoppedDate INTEGER, hash TEXT, UNIQUE (instance, hash) );
+                INSERT INTO file_new (id, serverId, addedDate, finishDate, instance, uploadRatio, seedRatioLimit, seedRatioMode, secondsSeeding, activityDate, finishDateEstimated, stoppedDate) SELECT id, serverId, addedDate, finishDate, instance, uploadRatio, seedRatioLimit, seedRatioMode, secondsSeeding, activityDate, finishDateEstimated, stoppedDate FROM file;
+                DROP TABLE file;
+                ALTER TABLE file_new RENAME TO file;",
+            )
+            .expect("Error adding hash to file table");
+    }
+
+    fn version(&self) -> u16 {
+        11
+    }
+
+    fn description(&self) -> String {
+        "Add hash to file table".to_string()
+    }
+}
+
+pub struct MetadataMigration {}
+
+#[async_trait::async_trait]
+impl Migration for MetadataMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Adding torrent metadata to file and plan tables...");
+        // trackers and labels are stored as json arrays
+        connection
+            .lock()
+            .await
+            .execute_batch(
+                "ALTER TABLE file ADD COLUMN name TEXT NOT NULL DEFAULT '';
+                ALTER TABLE file ADD COLUMN size INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE file ADD COLUMN downloadDir TEXT NOT NULL DEFAULT '';
+                ALTER TABLE file ADD COLUMN trackers TEXT NOT NULL DEFAULT '[]';
+                ALTER TABLE file ADD COLUMN labels TEXT NOT NULL DEFAULT '[]';
+                ALTER TABLE plan ADD COLUMN name TEXT NOT NULL DEFAULT '';",
+            )
+            .expect("Error adding torrent metadata to file and plan tables");
+    }
+
+    fn version(&self) -> u16 {
+        12
+    }
+
+    fn description(&self) -> String {
+        "Add torrent metadata to file and plan tables".to_string()
+    }
+}
+
+pub struct DeletionHistoryMigration {}
+
+#[async_trait::async_trait]
+impl Migration for DeletionHistoryMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Creating deletion_history table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "CREATE TABLE deletion_history ( id INTEGER PRIMARY KEY, instance TEXT NOT NULL, hash TEXT NOT NULL, name TEXT NOT NULL, size INTEGER NOT NULL, reason TEXT NOT NULL, rule TEXT, mode TEXT NOT NULL, deletedDate INTEGER NOT NULL, result TEXT NOT NULL );",
+                [],
+            )
+            .expect("Error creating deletion_history table");
+    }
+
+    fn version(&self) -> u16 {
+        13
+    }
+
+    fn description(&self) -> String {
+        "Add deletion_history table".to_string()
+    }
+}
+
+pub struct DigestMigration {}
+
+#[async_trait::async_trait]
+impl Migration for DigestMigration {
+    async fn apply(&self, connection: Arc<Mutex<Connection>>) {
+        println!("Creating digest table...");
+        connection
+            .lock()
+            .await
+            .execute(
+                "CREATE TABLE digest ( id INTEGER PRIMARY KEY, sentDate INTEGER NOT NULL );",
+                [],
+            )
+            .expect("Error creating digest table");
+    }
+
+    fn version(&self) -> u16 {
+        14
+    }
+
+    fn description(&self) -> String {
+        "Add digest table".to_string()
+    }
+}
 // MIGRATIONS END
 
 pub struct MigrationsManager {
@@ -42,10 +377,27 @@ pub struct MigrationsManager {
 
 impl MigrationsManager {
     pub fn new() -> Self {
-        MigrationsManager { current_version: 1 }
+        MigrationsManager {
+            current_version: 14,
+        }
     }
 
     pub fn get_migrations(&self) -> Vec<Box<dyn Migration>> {
-        vec![Box::new(InitialMigration {})]
+        vec![
+            Box::new(InitialMigration {}),
+            Box::new(InstanceMigration {}),
+            Box::new(PlanMigration {}),
+            Box::new(PlanRuleMigration {}),
+            Box::new(RatioMigration {}),
+            Box::new(SeedingTimesMigration {}),
+            Box::new(PlanModeMigration {}),
+            Box::new(TrashMigration {}),
+            Box::new(ProtectedMigration {}),
+            Box::new(StoppedDateMigration {}),
+            Box::new(HashMigration {}),
+            Box::new(MetadataMigration {}),
+            Box::new(DeletionHistoryMigration {}),
+            Box::new(DigestMigration {}),
+        ]
     }
 }
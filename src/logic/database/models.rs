@@ -5,10 +5,109 @@ pub struct MigrationVersion {
     pub description: String,
 }
 
-#[derive(Debug, Clone)]
+/// Seed ratio modes of a torrent, as defined by Transmission.
+pub const SEED_RATIO_MODE_GLOBAL: i32 = 0;
+pub const SEED_RATIO_MODE_SINGLE: i32 = 1;
+pub const SEED_RATIO_MODE_UNLIMITED: i32 = 2;
+
+#[derive(Debug, Clone, Default)]
 pub struct File {
     pub id: i32,
+    pub instance: String,
+    /// Id of the torrent in the client, it can change when the client restarts.
     pub server_id: i32,
+    /// Info-hash of the torrent in lowercase, the stable key of the torrent.
+    pub hash: String,
     pub added_date: i64,
     pub finish_date: Option<i64>,
+    /// The finish date is the time the torrent was first seen finished, not the one reported
+    /// by the client.
+    pub finish_date_estimated: bool,
+    pub seconds_seeding: i64,
+    pub activity_date: Option<i64>,
+    pub upload_ratio: f64,
+    /// Ratio limit of the torrent, only used with `SEED_RATIO_MODE_SINGLE`.
+    pub seed_ratio_limit: f64,
+    pub seed_ratio_mode: i32,
+    /// When the service stopped the torrent, stopped torrents are removed after the stop
+    /// lifetime.
+    pub stopped_date: Option<i64>,
+
+    // metadata reported by the torrent client on each scan, `private` is not stored
+    pub name: String,
+    pub size: i64,
+    pub download_dir: String,
+    pub labels: Vec<String>,
+    /// Hosts of the announce urls.
+    pub trackers: Vec<String>,
+    pub private: bool,
+}
+
+/// Human readable identity of the torrent used in the logs.
+impl std::fmt::Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let short_hash = &self.hash[..self.hash.len().min(8)];
+        match (self.name.is_empty(), short_hash.is_empty()) {
+            (false, false) => write!(f, "{} ({})", self.name, short_hash),
+            (false, true) => write!(f, "{}", self.name),
+            (true, false) => write!(f, "{}", self.hash),
+            (true, true) => write!(f, "#{}", self.server_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlanEntry {
+    pub id: i32,
+    pub instance: String,
+    pub server_id: i32,
+    /// Name of the torrent.
+    pub name: String,
+    pub reason: String,
+    pub due_date: i64,
+    pub planned_date: i64,
+    /// Name of the rule that set the lifetimes, if any.
+    pub rule: Option<String>,
+    /// `delete_data` or `keep_data`.
+    pub mode: String,
+}
+
+/// Data of a removed torrent waiting in the trash to be purged.
+#[derive(Debug, Clone, Default)]
+pub struct TrashEntry {
+    pub id: i32,
+    pub instance: String,
+    pub name: String,
+    /// Directory holding the data in the trash.
+    pub path: String,
+    pub size: i64,
+    pub trashed_date: i64,
+}
+
+/// Torrents that are never removed, matched by info-hash, name pattern or label.
+#[derive(Debug, Clone, Default)]
+pub struct Protection {
+    pub id: i32,
+    /// `hash`, `name` or `label`.
+    pub kind: String,
+    pub value: String,
+    pub created_date: i64,
+}
+
+/// Removal of a torrent by the service, kept after the torrent is gone.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryEntry {
+    pub id: i32,
+    pub instance: String,
+    pub hash: String,
+    pub name: String,
+    pub size: i64,
+    pub reason: String,
+    /// Name of the rule that set the lifetimes, if any.
+    pub rule: Option<String>,
+    /// `delete_data`, `keep_data` or `trash`.
+    pub mode: String,
+    pub deleted_date: i64,
+    /// `success`, or the error returned by the torrent client.
+    pub result: String,
 }
@@ -0,0 +1,317 @@
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+
+use crate::logic::database::models::HistoryEntry;
+use crate::logic::history::format_date;
+use crate::logic::status::TrackedTorrent;
+
+/// Time the SMTP server has to answer before the email is given up.
+const SMTP_TIMEOUT_SECS: u64 = 30;
+
+/// How often the digest is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Daily,
+    Weekly,
+}
+
+impl DigestPeriod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "daily" => Some(DigestPeriod::Daily),
+            "weekly" => Some(DigestPeriod::Weekly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestPeriod::Daily => "daily",
+            DigestPeriod::Weekly => "weekly",
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            DigestPeriod::Daily => 86400,
+            DigestPeriod::Weekly => 7 * 86400,
+        }
+    }
+}
+
+/// Security of the connection to the SMTP server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS, which is required.
+    #[default]
+    Starttls,
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// Plain connection, only for local relays and test sinks.
+    None,
+}
+
+impl SmtpTls {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "starttls" => Some(SmtpTls::Starttls),
+            "tls" => Some(SmtpTls::Tls),
+            "none" => Some(SmtpTls::None),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SmtpTls::Starttls => "starttls",
+            SmtpTls::Tls => "tls",
+            SmtpTls::None => "none",
+        }
+    }
+}
+
+/// SMTP server and recipients of the emails.
+#[derive(Debug, Clone, Default)]
+pub struct EmailSettings {
+    pub host: String,
+    /// Defaults to 587 with STARTTLS, 465 with TLS and 25 otherwise.
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Send a digest of the removals every period, if set.
+    pub digest: Option<DigestPeriod>,
+    /// Send an email as soon as a removal or a scan fails.
+    pub alerts: bool,
+}
+
+/// Sends the digests and alerts through the SMTP server.
+pub struct Mailer {
+    settings: EmailSettings,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl Mailer {
+    /// Check the addresses and prepare the connection, nothing is sent yet.
+    pub fn new(settings: EmailSettings) -> Result<Self, String> {
+        let parse = |address: &str| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|e| format!("Invalid email address '{}': {}", address, e))
+        };
+        let from = parse(&settings.from)?;
+        let to = settings
+            .to
+            .iter()
+            .map(|address| parse(address))
+            .collect::<Result<Vec<_>, _>>()?;
+        if to.is_empty() {
+            return Err("Emails require at least one recipient".to_string());
+        }
+
+        let mut builder = match settings.tls {
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &settings.host,
+            )),
+        }
+        .map_err(|e| format!("Invalid SMTP server '{}': {}", settings.host, e))?
+        .timeout(Some(std::time::Duration::from_secs(SMTP_TIMEOUT_SECS)));
+        if let Some(port) = settings.port {
+            builder = builder.port(port);
+        } else if settings.tls == SmtpTls::None {
+            builder = builder.port(25);
+        }
+        if let Some(username) = &settings.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                settings.password.clone().unwrap_or_default(),
+            ));
+        }
+
+        Ok(Mailer {
+            settings,
+            from,
+            to,
+            transport: builder.build(),
+        })
+    }
+
+    pub fn settings(&self) -> &EmailSettings {
+        &self.settings
+    }
+
+    pub async fn send(&self, subject: &str, body: String) -> Result<(), String> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message
+            .body(body)
+            .map_err(|e| format!("Failed to build the email: {}", e))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| format!("Failed to send the email: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Body of the digest of the removals between `from` and `to`, with the torrents expiring in the
+/// next 24 hours.
+pub fn digest_body(
+    history: &[HistoryEntry],
+    expiring: &[&TrackedTorrent],
+    from: i64,
+    to: i64,
+) -> String {
+    let (removed, failed): (Vec<&HistoryEntry>, Vec<&HistoryEntry>) =
+        history.iter().partition(|entry| entry.result == "success");
+    let freed: i64 = removed
+        .iter()
+        .filter(|entry| entry.mode == "delete_data")
+        .map(|entry| entry.size)
+        .sum();
+
+    let mut body = format!(
+        "Removals from {} to {} (UTC)\n\nSpace freed: {}\n",
+        format_date(from),
+        format_date(to),
+        format_size(freed)
+    );
+
+    body.push_str(&format!("\nRemoved torrents ({}):\n", removed.len()));
+    for entry in &removed {
+        body.push_str(&format!(
+            "  {}  [{}] {}  {}  {}  {}\n",
+            format_date(entry.deleted_date),
+            entry.instance,
+            entry.name,
+            format_size(entry.size),
+            entry.reason,
+            entry.mode
+        ));
+    }
+    if !failed.is_empty() {
+        body.push_str(&format!("\nFailed removals ({}):\n", failed.len()));
+        for entry in &failed {
+            body.push_str(&format!(
+                "  {}  [{}] {}  {}\n",
+                format_date(entry.deleted_date),
+                entry.instance,
+                entry.name,
+                entry.result
+            ));
+        }
+    }
+
+    body.push_str(&format!(
+        "\nExpiring in the next 24 hours ({}):\n",
+        expiring.len()
+    ));
+    for torrent in expiring {
+        body.push_str(&format!(
+            "  {}  [{}] {}  {}  {}\n",
+            format_date(torrent.expiry_date.unwrap_or_default()),
+            torrent.instance,
+            torrent.name,
+            format_size(torrent.size),
+            torrent.reason.as_deref().unwrap_or_default()
+        ));
+    }
+    body
+}
+
+/// Readable size in binary units.
+pub fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_digest_body() {
+        let entry = |name: &str, mode: &str, result: &str| HistoryEntry {
+            instance: "box1".to_string(),
+            name: name.to_string(),
+            size: 1024 * 1024,
+            reason: "ratio".to_string(),
+            mode: mode.to_string(),
+            deleted_date: 86400 + 3600,
+            result: result.to_string(),
+            ..Default::default()
+        };
+        let history = vec![
+            entry("debian.iso", "delete_data", "success"),
+            entry("kept.iso", "keep_data", "success"),
+            entry("trashed.iso", "trash", "success"),
+            entry("ubuntu.iso", "delete_data", "Connection refused"),
+        ];
+        let expiring = TrackedTorrent {
+            instance: "box1".to_string(),
+            hash: String::new(),
+            name: "arch.iso".to_string(),
+            size: 2048,
+            download_dir: String::new(),
+            added_date: 0,
+            finish_date: None,
+            stopped_date: None,
+            protected: false,
+            reason: Some("lifetime".to_string()),
+            rule: None,
+            mode: None,
+            expiry_date: Some(2 * 86400),
+        };
+
+        let body = digest_body(&history, &[&expiring], 86400, 2 * 86400);
+        assert!(body.starts_with("Removals from 1970-01-02 00:00:00 to 1970-01-03 00:00:00"));
+        // the data of the kept and trashed torrents is not freed
+        assert!(body.contains("Space freed: 1.0 MiB\n"));
+        assert!(body.contains("Removed torrents (3):\n"));
+        assert!(
+            body.contains(
+                "  1970-01-02 01:00:00  [box1] debian.iso  1.0 MiB  ratio  delete_data\n"
+            )
+        );
+        assert!(body.contains("Failed removals (1):\n"));
+        assert!(body.contains("[box1] ubuntu.iso  Connection refused\n"));
+        assert!(body.contains("Expiring in the next 24 hours (1):\n"));
+        assert!(body.contains("  1970-01-03 00:00:00  [box1] arch.iso  2.0 KiB  lifetime\n"));
+    }
+}
@@ -0,0 +1,101 @@
+/// Filter of the deletion history, unset values match everything.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Deletions at or after this timestamp.
+    pub from: Option<i64>,
+    /// Deletions before this timestamp.
+    pub to: Option<i64>,
+    /// Part of the torrent name, case insensitive.
+    pub name: Option<String>,
+}
+
+/// Parse a unix timestamp or a `YYYY-MM-DD` date in UTC. With `end_of_day` the value is
+/// the first second after it, so that it is included when used as the exclusive end of a range.
+pub fn parse_date(value: &str, end_of_day: bool) -> Result<i64, String> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(timestamp + if end_of_day { 1 } else { 0 });
+    }
+
+    let invalid = || {
+        format!(
+            "Invalid date '{}', expected YYYY-MM-DD or a timestamp",
+            value
+        )
+    };
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let year = year.parse::<i64>().map_err(|_| invalid())?;
+    let month = month.parse::<i64>().map_err(|_| invalid())?;
+    let day = day.parse::<i64>().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+
+    let days = days_from_civil(year, month, day) + if end_of_day { 1 } else { 0 };
+    Ok(days * 86400)
+}
+
+/// Format a unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn format_date(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+    let seconds = timestamp.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// conversions between days since the epoch and dates of the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1763580763", false), Ok(1763580763));
+        assert_eq!(parse_date("1763580763", true), Ok(1763580764));
+        assert_eq!(parse_date("1970-01-01", false), Ok(0));
+        assert_eq!(parse_date("2025-11-19", false), Ok(1763510400));
+        assert_eq!(parse_date("2025-11-19", true), Ok(1763596800));
+        assert_eq!(parse_date("2024-02-29", false), Ok(1709164800));
+        assert!(parse_date("2025-13-01", false).is_err());
+        assert!(parse_date("yesterday", false).is_err());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01 00:00:00");
+        assert_eq!(format_date(1763580763), "2025-11-19 19:32:43");
+        assert_eq!(format_date(1709164800 + 86399), "2024-02-29 23:59:59");
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::logic::status::ScanResult;
+
+/// Counters and gauges of the service, rendered in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    scans: BTreeMap<String, u64>,
+    scan_errors: BTreeMap<String, u64>,
+    /// Sum in seconds and count of the scan durations.
+    scan_duration: BTreeMap<String, (f64, u64)>,
+    last_success: BTreeMap<String, i64>,
+    rpc_errors: BTreeMap<(String, String), u64>,
+    tracked: BTreeMap<String, usize>,
+    pending: BTreeMap<String, usize>,
+    deleted: BTreeMap<(String, String, String), u64>,
+    bytes_freed: BTreeMap<(String, String), i64>,
+}
+
+impl Metrics {
+    /// Count a finished scan, the gauges are only updated by successful scans.
+    pub fn record_scan(&mut self, scan: &ScanResult) {
+        let instance = scan.instance.clone();
+        *self.scans.entry(instance.clone()).or_default() += 1;
+        let duration = self.scan_duration.entry(instance.clone()).or_default();
+        duration.0 += scan.duration_ms as f64 / 1000.0;
+        duration.1 += 1;
+        if scan.success {
+            self.last_success
+                .insert(instance.clone(), scan.started_date);
+            self.tracked.insert(instance.clone(), scan.tracked);
+            self.pending.insert(instance, scan.pending);
+        } else {
+            *self.scan_errors.entry(instance).or_default() += 1;
+        }
+    }
+
+    /// Count a failed call to the torrent client, e.g. `fetch` or `delete`.
+    pub fn record_rpc_error(&mut self, instance: &str, operation: &str) {
+        *self
+            .rpc_errors
+            .entry((instance.to_string(), operation.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Count a torrent removed from the client, its data is only freed if it was deleted, kept
+    /// and trashed data stays on the disk.
+    pub fn record_deleted(&mut self, instance: &str, reason: &str, mode: &str, size: i64) {
+        *self
+            .deleted
+            .entry((instance.to_string(), reason.to_string(), mode.to_string()))
+            .or_default() += 1;
+        if mode == "delete_data" {
+            self.record_freed(instance, reason, size);
+        }
+    }
+
+    /// Count the data of a trash entry deleted for good, with the `trash` reason.
+    pub fn record_purged(&mut self, instance: &str, size: i64) {
+        self.record_freed(instance, "trash", size);
+    }
+
+    fn record_freed(&mut self, instance: &str, reason: &str, size: i64) {
+        *self
+            .bytes_freed
+            .entry((instance.to_string(), reason.to_string()))
+            .or_default() += size;
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        header(&mut output, "fp_scans_total", "counter", "Scans run.");
+        for (instance, count) in &self.scans {
+            sample(
+                &mut output,
+                "fp_scans_total",
+                &[("instance", instance)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_scan_errors_total",
+            "counter",
+            "Scans that failed.",
+        );
+        for (instance, count) in &self.scan_errors {
+            sample(
+                &mut output,
+                "fp_scan_errors_total",
+                &[("instance", instance)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_scan_duration_seconds",
+            "summary",
+            "Duration of the scans.",
+        );
+        for (instance, (sum, count)) in &self.scan_duration {
+            let labels = [("instance", instance.as_str())];
+            sample(&mut output, "fp_scan_duration_seconds_sum", &labels, sum);
+            sample(
+                &mut output,
+                "fp_scan_duration_seconds_count",
+                &labels,
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_last_successful_scan_timestamp_seconds",
+            "gauge",
+            "Start of the last successful scan.",
+        );
+        for (instance, date) in &self.last_success {
+            sample(
+                &mut output,
+                "fp_last_successful_scan_timestamp_seconds",
+                &[("instance", instance)],
+                date,
+            );
+        }
+        header(
+            &mut output,
+            "fp_rpc_errors_total",
+            "counter",
+            "Failed calls to the torrent client.",
+        );
+        for ((instance, operation), count) in &self.rpc_errors {
+            sample(
+                &mut output,
+                "fp_rpc_errors_total",
+                &[("instance", instance), ("operation", operation)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_torrents_tracked",
+            "gauge",
+            "Torrents tracked.",
+        );
+        for (instance, count) in &self.tracked {
+            sample(
+                &mut output,
+                "fp_torrents_tracked",
+                &[("instance", instance)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_torrents_pending_expiry",
+            "gauge",
+            "Tracked torrents whose removal is not due yet.",
+        );
+        for (instance, count) in &self.pending {
+            sample(
+                &mut output,
+                "fp_torrents_pending_expiry",
+                &[("instance", instance)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_torrents_deleted_total",
+            "counter",
+            "Torrents removed from the torrent client.",
+        );
+        for ((instance, reason, mode), count) in &self.deleted {
+            sample(
+                &mut output,
+                "fp_torrents_deleted_total",
+                &[("instance", instance), ("reason", reason), ("mode", mode)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "fp_bytes_freed_total",
+            "counter",
+            "Bytes of data deleted, when the torrents are removed or purged from the trash.",
+        );
+        for ((instance, reason), bytes) in &self.bytes_freed {
+            sample(
+                &mut output,
+                "fp_bytes_freed_total",
+                &[("instance", instance), ("reason", reason)],
+                bytes,
+            );
+        }
+        output
+    }
+}
+
+fn header(output: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+}
+
+fn sample(output: &mut String, name: &str, labels: &[(&str, &str)], value: impl ToString) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", key, value)
+        })
+        .collect();
+    let _ = writeln!(
+        output,
+        "{}{{{}}} {}",
+        name,
+        labels.join(","),
+        value.to_string()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        metrics.record_scan(&ScanResult {
+            instance: "box1".to_string(),
+            started_date: 100,
+            duration_ms: 1500,
+            success: true,
+            tracked: 3,
+            pending: 2,
+            ..Default::default()
+        });
+        metrics.record_scan(&ScanResult {
+            instance: "box1".to_string(),
+            started_date: 200,
+            duration_ms: 500,
+            success: false,
+            ..Default::default()
+        });
+        metrics.record_rpc_error("box1", "fetch");
+        metrics.record_deleted("box1", "ratio", "delete_data", 1000);
+        metrics.record_deleted("box1", "ratio", "trash", 500);
+        metrics.record_deleted("box1", "lifetime", "keep_data", 2000);
+        metrics.record_purged("box1", 500);
+
+        let output = metrics.render();
+        assert!(output.contains("# TYPE fp_scans_total counter\n"));
+        assert!(output.contains("fp_scans_total{instance=\"box1\"} 2\n"));
+        assert!(output.contains("fp_scan_errors_total{instance=\"box1\"} 1\n"));
+        assert!(output.contains("fp_scan_duration_seconds_sum{instance=\"box1\"} 2\n"));
+        assert!(output.contains("fp_scan_duration_seconds_count{instance=\"box1\"} 2\n"));
+        // the gauges keep the values of the last successful scan
+        assert!(
+            output.contains("fp_last_successful_scan_timestamp_seconds{instance=\"box1\"} 100\n")
+        );
+        assert!(output.contains("fp_torrents_tracked{instance=\"box1\"} 3\n"));
+        assert!(output.contains("fp_torrents_pending_expiry{instance=\"box1\"} 2\n"));
+        assert!(output.contains("fp_rpc_errors_total{instance=\"box1\",operation=\"fetch\"} 1\n"));
+        assert!(output.contains(
+            "fp_torrents_deleted_total{instance=\"box1\",reason=\"ratio\",mode=\"trash\"} 1\n"
+        ));
+        // trashed data is only freed once purged
+        assert!(output.contains("fp_bytes_freed_total{instance=\"box1\",reason=\"ratio\"} 1000\n"));
+        assert!(output.contains("fp_bytes_freed_total{instance=\"box1\",reason=\"trash\"} 500\n"));
+        assert!(!output.contains("fp_bytes_freed_total{instance=\"box1\",reason=\"lifetime\"}"));
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        let mut metrics = Metrics::default();
+        metrics.record_rpc_error("my \"box\"", "fetch");
+        assert!(
+            metrics.render().contains(
+                "fp_rpc_errors_total{instance=\"my \\\"box\\\"\",operation=\"fetch\"} 1\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logic::plan::Removal;
+
+/// Time the torrent client scans wait for a webhook before giving up.
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+/// What happened to the torrents of an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Torrents were removed from the client, or moved to the trash.
+    Deleted,
+    /// The torrent client failed to remove torrents.
+    DeleteFailed,
+    /// A scan failed, e.g. the torrent client is unreachable.
+    ScanFailed,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Deleted => "deleted",
+            EventKind::DeleteFailed => "delete_failed",
+            EventKind::ScanFailed => "scan_failed",
+        }
+    }
+}
+
+/// A torrent an event is about.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventTorrent {
+    pub hash: String,
+    pub name: String,
+    pub size: i64,
+    pub reason: String,
+    pub rule: Option<String>,
+}
+
+impl EventTorrent {
+    pub fn from_removal(removal: &Removal) -> Self {
+        EventTorrent {
+            hash: removal.file.hash.clone(),
+            name: removal.file.name.clone(),
+            size: removal.file.size,
+            reason: removal.reason.as_str().to_string(),
+            rule: removal.rule.clone(),
+        }
+    }
+}
+
+/// Event sent to the notifiers.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub event: EventKind,
+    pub instance: String,
+    pub date: i64,
+    /// Removal mode of the torrents, if the event is about removed torrents.
+    pub mode: Option<String>,
+    pub torrents: Vec<EventTorrent>,
+    pub error: Option<String>,
+}
+
+impl Event {
+    /// Readable summary of the event, used by the chat presets.
+    pub fn message(&self) -> String {
+        let names = self
+            .torrents
+            .iter()
+            .map(|torrent| torrent.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mode = self.mode.as_deref().unwrap_or_default();
+        match self.event {
+            EventKind::Deleted => format!(
+                "[{}] Removed {} torrent(s) ({}): {}",
+                self.instance,
+                self.torrents.len(),
+                mode,
+                names
+            ),
+            EventKind::DeleteFailed => format!(
+                "[{}] Failed to remove {} torrent(s) ({}): {}, error: {}",
+                self.instance,
+                self.torrents.len(),
+                mode,
+                names,
+                self.error.as_deref().unwrap_or_default()
+            ),
+            EventKind::ScanFailed => format!(
+                "[{}] Scan failed: {}",
+                self.instance,
+                self.error.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Total size in bytes of the torrents.
+    pub fn size(&self) -> i64 {
+        self.torrents.iter().map(|torrent| torrent.size).sum()
+    }
+}
+
+/// Format of the webhook payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookPreset {
+    /// The event as JSON.
+    #[default]
+    Json,
+    Discord,
+    Slack,
+    /// Plain text message published to a ntfy topic url.
+    Ntfy,
+}
+
+impl WebhookPreset {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(WebhookPreset::Json),
+            "discord" => Some(WebhookPreset::Discord),
+            "slack" => Some(WebhookPreset::Slack),
+            "ntfy" => Some(WebhookPreset::Ntfy),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookPreset::Json => "json",
+            WebhookPreset::Discord => "discord",
+            WebhookPreset::Slack => "slack",
+            WebhookPreset::Ntfy => "ntfy",
+        }
+    }
+
+    fn template(&self) -> &'static str {
+        match self {
+            WebhookPreset::Json => "{{json}}",
+            WebhookPreset::Discord => "{\"content\": \"{{message}}\"}",
+            WebhookPreset::Slack => "{\"text\": \"{{message}}\"}",
+            WebhookPreset::Ntfy => "{{message}}",
+        }
+    }
+}
+
+/// Url the events are posted to.
+#[derive(Debug, Clone, Default)]
+pub struct Webhook {
+    pub url: String,
+    pub preset: WebhookPreset,
+    /// Extra headers, e.g. for authentication.
+    pub headers: Vec<(String, String)>,
+    /// Body template replacing the one of the preset, see `render`.
+    pub template: Option<String>,
+}
+
+impl Webhook {
+    /// Render the body of the event. The `{{event}}`, `{{instance}}`, `{{message}}`,
+    /// `{{mode}}`, `{{error}}`, `{{torrents}}` (names), `{{count}}`, `{{size}}` and `{{date}}`
+    /// placeholders are escaped to fit in JSON strings, `{{json}}` is the whole event as JSON.
+    pub fn render(&self, event: &Event) -> String {
+        let template = self.template.as_deref().unwrap_or(self.preset.template());
+        // plain text bodies are not escaped
+        let escape = |value: &str| {
+            if self.preset == WebhookPreset::Ntfy && self.template.is_none() {
+                value.to_string()
+            } else {
+                let quoted = serde_json::to_string(value).unwrap_or_default();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+        };
+        let names = event
+            .torrents
+            .iter()
+            .map(|torrent| torrent.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // placeholders are replaced in a single pass, so values are never expanded again
+        let mut body = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            body.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find("}}") else {
+                // an unclosed placeholder is kept as is
+                rest = &rest[start..];
+                break;
+            };
+            let key = &rest[start + 2..start + end];
+            let value = match key.trim() {
+                "json" => serde_json::to_string(event).unwrap_or_default(),
+                "event" => event.event.as_str().to_string(),
+                "instance" => escape(&event.instance),
+                "message" => escape(&event.message()),
+                "mode" => escape(event.mode.as_deref().unwrap_or_default()),
+                "error" => escape(event.error.as_deref().unwrap_or_default()),
+                "torrents" => escape(&names),
+                "count" => event.torrents.len().to_string(),
+                "size" => event.size().to_string(),
+                "date" => event.date.to_string(),
+                // unknown placeholders are kept as is
+                _ => rest[start..start + end + 2].to_string(),
+            };
+            body.push_str(&value);
+            rest = &rest[start + end + 2..];
+        }
+        body.push_str(rest);
+        body
+    }
+
+    pub async fn send(&self, client: &reqwest::Client, event: &Event) -> Result<(), String> {
+        let mut request = client
+            .post(&self.url)
+            .timeout(std::time::Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+            .body(self.render(event));
+        if !self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        {
+            let content_type = if self.preset == WebhookPreset::Ntfy && self.template.is_none() {
+                "text/plain"
+            } else {
+                "application/json"
+            };
+            request = request.header("Content-Type", content_type);
+        }
+        if self.preset == WebhookPreset::Ntfy {
+            request = request.header("Title", format!("file_purge: {}", event.event.as_str()));
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        // the url is not logged, it often holds a token
+        let response = request
+            .send()
+            .await
+            .map_err(|e| e.without_url().to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Unexpected status {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `Name: value` header.
+pub fn parse_header(value: &str) -> Option<(String, String)> {
+    let (name, value) = value.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> Event {
+        Event {
+            event: EventKind::Deleted,
+            instance: "box1".to_string(),
+            date: 100,
+            mode: Some("delete_data".to_string()),
+            torrents: vec![EventTorrent {
+                hash: "a".repeat(40),
+                name: "The \"Movie\" {{count}}.mkv".to_string(),
+                size: 1000,
+                reason: "ratio".to_string(),
+                rule: None,
+            }],
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_render_presets() {
+        let webhook = |preset| Webhook {
+            preset,
+            ..Default::default()
+        };
+
+        let body = webhook(WebhookPreset::Json).render(&event());
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["event"], "deleted");
+        assert_eq!(json["torrents"][0]["name"], "The \"Movie\" {{count}}.mkv");
+
+        let body = webhook(WebhookPreset::Discord).render(&event());
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            json["content"],
+            "[box1] Removed 1 torrent(s) (delete_data): The \"Movie\" {{count}}.mkv"
+        );
+
+        let body = webhook(WebhookPreset::Slack).render(&event());
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(json["text"].as_str().unwrap().starts_with("[box1] Removed"));
+
+        let body = webhook(WebhookPreset::Ntfy).render(&event());
+        assert_eq!(
+            body,
+            "[box1] Removed 1 torrent(s) (delete_data): The \"Movie\" {{count}}.mkv"
+        );
+    }
+
+    #[test]
+    fn test_render_template() {
+        let webhook = Webhook {
+            template: Some(
+                "{\"type\": \"{{event}}\", \"names\": \"{{torrents}}\", \"count\": {{count}}, \"bytes\": {{size}}}"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let json: serde_json::Value = serde_json::from_str(&webhook.render(&event())).unwrap();
+        assert_eq!(json["type"], "deleted");
+        assert_eq!(json["names"], "The \"Movie\" {{count}}.mkv");
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["bytes"], 1000);
+    }
+
+    #[test]
+    fn test_render_unclosed_placeholder() {
+        let webhook = Webhook {
+            template: Some("{{event}} of {{count".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(webhook.render(&event()), "deleted of {{count");
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("Authorization: Bearer abc:def"),
+            Some(("Authorization".to_string(), "Bearer abc:def".to_string()))
+        );
+        assert_eq!(parse_header("no separator"), None);
+        assert_eq!(parse_header(": value"), None);
+    }
+}
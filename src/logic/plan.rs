@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::logic::database::models::{File, SEED_RATIO_MODE_SINGLE};
+
+/// Why a torrent is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The torrent finished downloading longer than the lifetime after copied ago.
+    LifetimeAfterCopied,
+    /// The torrent was added longer than the lifetime ago.
+    Lifetime,
+    /// The finished torrent reached its ratio target.
+    Ratio,
+    /// The disk of the torrent is running out of space.
+    FreeSpace,
+}
+
+impl RemovalReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemovalReason::LifetimeAfterCopied => "lifetime_after_copied",
+            RemovalReason::Lifetime => "lifetime",
+            RemovalReason::Ratio => "ratio",
+            RemovalReason::FreeSpace => "free_space",
+        }
+    }
+
+    /// The torrent is removed as soon as the reason applies, whatever its due date.
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, RemovalReason::Ratio | RemovalReason::FreeSpace)
+    }
+}
+
+/// Lifetimes and ratio target used to compute when a torrent is removed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Policy {
+    pub files_lifetime: u32,
+    pub files_lifetime_after_copied: u32,
+    /// Ratio after which finished torrents are removed.
+    pub ratio: Option<f64>,
+    /// Also remove finished torrents that reached the ratio limit set on them in the client.
+    pub client_ratio_limit: bool,
+    /// Delete the local data of the removed torrents, or only remove them from the client.
+    pub delete_data: bool,
+    /// Move the data to the trash instead of deleting it.
+    pub trash: bool,
+    /// Stop the torrents once the lifetime after copied expires instead of removing them.
+    pub stop: bool,
+    /// Time in seconds after the download finished before stopped torrents are removed, they
+    /// are never removed if unset.
+    pub stop_lifetime: Option<u32>,
+}
+
+impl Policy {
+    /// Ratio after which the finished file is removed, if any.
+    pub fn ratio_target(&self, file: &File) -> Option<f64> {
+        let client_limit =
+            if self.client_ratio_limit && file.seed_ratio_mode == SEED_RATIO_MODE_SINGLE {
+                Some(file.seed_ratio_limit)
+            } else {
+                None
+            };
+
+        match (self.ratio, client_limit) {
+            (Some(ratio), Some(limit)) => Some(ratio.min(limit)),
+            (ratio, limit) => ratio.or(limit),
+        }
+    }
+}
+
+/// When and why a tracked torrent is going to be removed.
+#[derive(Debug, Clone)]
+pub struct Removal {
+    pub file: File,
+    pub reason: RemovalReason,
+    pub due_date: i64,
+    /// Name of the rule that set the lifetimes, if any.
+    pub rule: Option<String>,
+    /// The local data is deleted with the torrent.
+    pub delete_data: bool,
+    /// The local data is moved to the trash before the torrent is removed.
+    pub trash: bool,
+    /// The torrent is only stopped, it is removed once the stop lifetime expires.
+    pub stop: bool,
+    /// Date the stopped torrent is removed, never if unset.
+    pub stop_due_date: Option<i64>,
+}
+
+impl Removal {
+    /// Compute the removal of a file given its policy.
+    pub fn for_file(file: &File, policy: &Policy, current_time: i64) -> Self {
+        let lifetime_due = file.added_date + policy.files_lifetime as i64;
+        let after_copied_due = file
+            .finish_date
+            .map(|finish_date| finish_date + policy.files_lifetime_after_copied as i64);
+
+        let (mut reason, mut due_date) = match after_copied_due {
+            // expired copied files are always removed because of the lifetime after copied
+            Some(due) if current_time > due || due <= lifetime_due => {
+                (RemovalReason::LifetimeAfterCopied, due)
+            }
+            _ => (RemovalReason::Lifetime, lifetime_due),
+        };
+
+        // finished files that seeded enough are removed right away
+        if current_time <= due_date
+            && file.finish_date.is_some()
+            && policy
+                .ratio_target(file)
+                .is_some_and(|target| file.upload_ratio >= target)
+        {
+            reason = RemovalReason::Ratio;
+            due_date = current_time;
+        }
+
+        // copied files are stopped first and only removed after the stop lifetime
+        let mut stop = false;
+        let mut stop_due_date = None;
+        if policy.stop && reason == RemovalReason::LifetimeAfterCopied {
+            let stop_due = policy
+                .stop_lifetime
+                .zip(file.finish_date)
+                .map(|(lifetime, finish_date)| finish_date + lifetime as i64);
+            match stop_due {
+                Some(due) if current_time > due => due_date = due,
+                _ => {
+                    stop = true;
+                    stop_due_date = stop_due;
+                }
+            }
+        }
+
+        Removal {
+            file: file.clone(),
+            reason,
+            due_date,
+            rule: None,
+            delete_data: policy.delete_data,
+            trash: policy.trash && policy.delete_data,
+            stop,
+            stop_due_date,
+        }
+    }
+
+    /// The torrent must be removed, or stopped, now. Torrents already stopped are not due
+    /// until their stop lifetime expires.
+    pub fn is_due(&self, current_time: i64) -> bool {
+        !self.is_stopped() && (self.reason.is_immediate() || current_time > self.due_date)
+    }
+
+    /// Date of the next action on the torrent, stopped torrents expire with their stop lifetime.
+    pub fn expiry_date(&self) -> Option<i64> {
+        if self.is_stopped() {
+            self.stop_due_date
+        } else {
+            Some(self.due_date)
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop && self.file.stopped_date.is_some()
+    }
+
+    /// The torrent must be removed from the client now.
+    pub fn is_removal_due(&self, current_time: i64) -> bool {
+        !self.stop && self.is_due(current_time)
+    }
+
+    /// Removing the torrent frees its space on the disk, its data is neither kept nor trashed.
+    pub fn frees_space(&self) -> bool {
+        self.delete_data && !self.trash
+    }
+
+    pub fn mode(&self) -> &'static str {
+        if self.stop {
+            "stop"
+        } else if self.trash {
+            "trash"
+        } else {
+            removal_mode(self.delete_data)
+        }
+    }
+}
+
+/// Name of the removal mode, as stored in the database.
+pub fn removal_mode(delete_data: bool) -> &'static str {
+    if delete_data {
+        "delete_data"
+    } else {
+        "keep_data"
+    }
+}
+
+/// Which finished torrents are removed first when a disk runs out of space.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FreeSpaceOrder {
+    #[default]
+    Oldest,
+    Largest,
+}
+
+impl FreeSpaceOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "oldest" => Some(FreeSpaceOrder::Oldest),
+            "largest" => Some(FreeSpaceOrder::Largest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FreeSpaceOrder::Oldest => "oldest",
+            FreeSpaceOrder::Largest => "largest",
+        }
+    }
+}
+
+/// Free space to keep on the disks of the download directories.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FreeSpacePolicy {
+    /// Finished torrents are removed when the free space in bytes drops below this threshold.
+    pub min_free_space: i64,
+    /// Free space in bytes to reach once the removal started.
+    pub target_free_space: i64,
+    pub order: FreeSpaceOrder,
+}
+
+/// Remove finished torrents of the directories below the free space threshold until the target
+/// is reached, even if their lifetime did not expire yet. Only the removals deleting the data
+/// are counted, kept and trashed data stays on the disk.
+pub fn apply_free_space_pressure(
+    removals: &mut [Removal],
+    free_space: &HashMap<String, i64>,
+    policy: &FreeSpacePolicy,
+    current_time: i64,
+) {
+    for (directory, free) in free_space {
+        if *free >= policy.min_free_space {
+            continue;
+        }
+
+        let in_directory = |removal: &Removal| &removal.file.download_dir == directory;
+        // space released by the torrents that are removed anyway
+        let mut freed: i64 = removals
+            .iter()
+            .filter(|removal| {
+                in_directory(removal)
+                    && removal.frees_space()
+                    && removal.is_removal_due(current_time)
+            })
+            .map(|removal| removal.file.size)
+            .sum();
+
+        let mut candidates: Vec<usize> = (0..removals.len())
+            .filter(|index| {
+                let removal = &removals[*index];
+                in_directory(removal)
+                    && removal.frees_space()
+                    && removal.file.finish_date.is_some()
+                    && !removal.is_removal_due(current_time)
+            })
+            .collect();
+        match policy.order {
+            FreeSpaceOrder::Oldest => {
+                candidates.sort_by_key(|index| removals[*index].file.finish_date)
+            }
+            FreeSpaceOrder::Largest => {
+                candidates.sort_by_key(|index| std::cmp::Reverse(removals[*index].file.size))
+            }
+        }
+
+        for index in candidates {
+            if free + freed >= policy.target_free_space {
+                break;
+            }
+            let removal = &mut removals[index];
+            removal.reason = RemovalReason::FreeSpace;
+            removal.due_date = current_time;
+            removal.stop = false;
+            freed += removal.file.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removals(policy: &Policy) -> Vec<Removal> {
+        [(1, 300), (2, 500)]
+            .into_iter()
+            .map(|(server_id, size)| {
+                let file = File {
+                    server_id,
+                    added_date: 0,
+                    finish_date: Some(100),
+                    size,
+                    download_dir: "/downloads".to_string(),
+                    ..Default::default()
+                };
+                Removal::for_file(&file, policy, 200)
+            })
+            .collect()
+    }
+
+    fn pressure(removals: &mut [Removal]) {
+        let free_space = HashMap::from([("/downloads".to_string(), 100)]);
+        let policy = FreeSpacePolicy {
+            min_free_space: 200,
+            target_free_space: 500,
+            order: FreeSpaceOrder::Oldest,
+        };
+        apply_free_space_pressure(removals, &free_space, &policy, 200);
+    }
+
+    fn policy() -> Policy {
+        Policy {
+            files_lifetime: 10000,
+            files_lifetime_after_copied: 10000,
+            delete_data: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_free_space_pressure() {
+        let mut removals = removals(&policy());
+        pressure(&mut removals);
+        assert_eq!(removals[0].reason, RemovalReason::FreeSpace);
+        assert_eq!(removals[1].reason, RemovalReason::FreeSpace);
+    }
+
+    #[test]
+    fn test_free_space_pressure_keep_data() {
+        // removing the torrents without their data frees nothing
+        let mut removals = removals(&Policy {
+            delete_data: false,
+            ..policy()
+        });
+        pressure(&mut removals);
+        assert!(removals.iter().all(|removal| !removal.is_due(200)));
+    }
+
+    #[test]
+    fn test_free_space_pressure_trash() {
+        // the trashed data stays on the disk until it is purged
+        let mut removals = removals(&Policy {
+            trash: true,
+            ..policy()
+        });
+        pressure(&mut removals);
+        assert!(removals.iter().all(|removal| !removal.is_due(200)));
+    }
+}
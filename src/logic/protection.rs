@@ -0,0 +1,141 @@
+use regex::Regex;
+
+use crate::logic::database::models::{File, Protection};
+
+/// What a protection matches the torrents by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionKind {
+    /// Info-hash of the torrent.
+    Hash,
+    /// Regular expression matched against the torrent name.
+    Name,
+    Label,
+}
+
+impl ProtectionKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hash" => Some(ProtectionKind::Hash),
+            "name" => Some(ProtectionKind::Name),
+            "label" => Some(ProtectionKind::Label),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtectionKind::Hash => "hash",
+            ProtectionKind::Name => "name",
+            ProtectionKind::Label => "label",
+        }
+    }
+}
+
+/// Build a protection, the value is checked against its kind.
+pub fn new_protection(kind: &str, value: &str, current_time: i64) -> Result<Protection, String> {
+    let kind = ProtectionKind::parse(kind).ok_or(format!(
+        "Invalid protection kind '{}', expected hash, name or label",
+        kind
+    ))?;
+    let value = match kind {
+        ProtectionKind::Hash => {
+            // v1 hashes are 40 hex characters long, v2 hashes 64
+            if !matches!(value.len(), 40 | 64) || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid info-hash '{}'", value));
+            }
+            value.to_lowercase()
+        }
+        ProtectionKind::Name => {
+            Regex::new(value).map_err(|e| format!("Invalid name pattern: {}", e))?;
+            value.to_string()
+        }
+        ProtectionKind::Label => value.to_string(),
+    };
+    if value.is_empty() {
+        return Err("The protection value can not be empty".to_string());
+    }
+
+    Ok(Protection {
+        id: 0,
+        kind: kind.as_str().to_string(),
+        value,
+        created_date: current_time,
+    })
+}
+
+/// Protections loaded from the database, ready to be matched against the scanned torrents.
+#[derive(Debug, Default)]
+pub struct Protections {
+    hashes: Vec<String>,
+    names: Vec<Regex>,
+    labels: Vec<String>,
+}
+
+impl Protections {
+    /// Compile the protections, invalid ones are ignored.
+    pub fn new(protections: &[Protection]) -> Self {
+        let mut compiled = Protections::default();
+        for protection in protections {
+            match ProtectionKind::parse(&protection.kind) {
+                Some(ProtectionKind::Hash) => {
+                    compiled.hashes.push(protection.value.to_lowercase());
+                }
+                Some(ProtectionKind::Name) => {
+                    if let Ok(regex) = Regex::new(&protection.value) {
+                        compiled.names.push(regex);
+                    }
+                }
+                Some(ProtectionKind::Label) => compiled.labels.push(protection.value.clone()),
+                None => {}
+            }
+        }
+        compiled
+    }
+
+    pub fn is_protected(&self, file: &File) -> bool {
+        (!file.hash.is_empty() && self.hashes.contains(&file.hash))
+            || self.names.iter().any(|regex| regex.is_match(&file.name))
+            || file.labels.iter().any(|label| self.labels.contains(label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A";
+
+    #[test]
+    fn test_new_protection() {
+        let protection = new_protection("hash", HASH, 100).unwrap();
+        assert_eq!(protection.kind, "hash");
+        assert_eq!(protection.value, HASH.to_lowercase());
+        assert_eq!(protection.created_date, 100);
+
+        assert!(new_protection("hash", "c12fe1c0", 100).is_err());
+        assert!(new_protection("name", "(unclosed", 100).is_err());
+        assert!(new_protection("label", "", 100).is_err());
+        assert!(new_protection("tracker", "example.org", 100).is_err());
+    }
+
+    #[test]
+    fn test_is_protected() {
+        let protections = Protections::new(&[
+            new_protection("hash", HASH, 0).unwrap(),
+            new_protection("name", "(?i)^own release", 0).unwrap(),
+            new_protection("label", "seed-forever", 0).unwrap(),
+        ]);
+
+        let file = |hash: &str, name: &str, labels: &[&str]| File {
+            hash: hash.to_string(),
+            name: name.to_string(),
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(protections.is_protected(&file(&HASH.to_lowercase(), "a", &[])));
+        assert!(protections.is_protected(&file("", "Own Release 1.0", &[])));
+        assert!(protections.is_protected(&file("", "a", &["tv", "seed-forever"])));
+        assert!(!protections.is_protected(&file("", "a", &["tv"])));
+        assert!(!Protections::default().is_protected(&file("", "a", &[])));
+    }
+}
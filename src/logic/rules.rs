@@ -0,0 +1,433 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::logic::database::models::File;
+use crate::logic::plan::{Policy, Removal};
+
+/// What to do with the torrents matched by a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Delete the torrent and its data once a lifetime expires, even with keep data enabled,
+    /// without stopping it first.
+    Delete,
+    /// Remove the torrent once a lifetime expires but keep its data, without stopping it first.
+    Remove,
+    /// Never delete the torrent.
+    Keep,
+    /// Stop the torrent once the lifetime after copied expires, and remove it after the stop
+    /// lifetime like the instance removes it.
+    Stop,
+}
+
+/// Conditions a torrent must meet for a rule to apply, unset conditions match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleMatch {
+    pub instance: Option<String>,
+    /// Prefix of the download directory.
+    pub download_dir: Option<String>,
+    pub label: Option<String>,
+    /// Tracker host, subdomains of the host match too.
+    pub tracker: Option<String>,
+    /// Regular expression matched against the torrent name.
+    pub name: Option<String>,
+    /// Minimum total size in bytes.
+    pub min_size: Option<i64>,
+    /// Maximum total size in bytes.
+    pub max_size: Option<i64>,
+    pub private: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default, rename = "match")]
+    pub conditions: RuleMatch,
+    /// Overrides the lifetime of the instance.
+    pub lifetime: Option<u32>,
+    /// Overrides the lifetime after copied of the instance.
+    pub lifetime_after_copied: Option<u32>,
+    /// Overrides the ratio target of the instance.
+    pub ratio: Option<f64>,
+    /// Overrides the stop lifetime of the instance.
+    pub stop_lifetime: Option<u32>,
+    /// Unset, the torrent is removed once a lifetime expires like the instance removes it.
+    pub action: Option<RuleAction>,
+
+    #[serde(skip)]
+    name_regex: Option<Regex>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+impl Rule {
+    /// Compile the name expression, must be called before matching.
+    pub fn compile(&mut self) -> Result<(), String> {
+        self.name_regex = match &self.conditions.name {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .map_err(|e| format!("Invalid name pattern in rule '{}': {}", self.name, e))?,
+            ),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn matches(&self, instance: &str, file: &File) -> bool {
+        let conditions = &self.conditions;
+
+        conditions
+            .instance
+            .as_ref()
+            .is_none_or(|name| name == instance)
+            && conditions
+                .download_dir
+                .as_ref()
+                .is_none_or(|dir| file.download_dir.starts_with(dir.as_str()))
+            && conditions
+                .label
+                .as_ref()
+                .is_none_or(|label| file.labels.contains(label))
+            && conditions.tracker.as_ref().is_none_or(|host| {
+                file.trackers.iter().any(|tracker| {
+                    tracker == host || tracker.ends_with(format!(".{}", host).as_str())
+                })
+            })
+            && self
+                .name_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(&file.name))
+            && conditions.min_size.is_none_or(|size| file.size >= size)
+            && conditions.max_size.is_none_or(|size| file.size <= size)
+            && conditions
+                .private
+                .is_none_or(|private| file.private == private)
+    }
+}
+
+/// Parse the rules of a TOML document with `[[rule]]` tables.
+pub fn parse_rules(content: &str) -> Result<Vec<Rule>, String> {
+    let mut rules = toml::from_str::<RulesFile>(content)
+        .map_err(|e| format!("Invalid rules: {}", e))?
+        .rules;
+    for rule in rules.iter_mut() {
+        rule.compile()?;
+    }
+    Ok(rules)
+}
+
+pub fn load_rules(path: &str) -> Result<Vec<Rule>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read rules file {}: {}", path, e))?;
+    parse_rules(&content)
+}
+
+/// Compute the removal of a file using the first matching rule, or the policy of the instance
+/// if no rule matches. Returns `None` if the file must be kept.
+pub fn removal_for(
+    rules: &[Rule],
+    instance: &str,
+    file: &File,
+    policy: &Policy,
+    current_time: i64,
+) -> Option<Removal> {
+    match rules.iter().find(|rule| rule.matches(instance, file)) {
+        Some(rule) if rule.action == Some(RuleAction::Keep) => None,
+        Some(rule) => {
+            let policy = Policy {
+                files_lifetime: rule.lifetime.unwrap_or(policy.files_lifetime),
+                files_lifetime_after_copied: rule
+                    .lifetime_after_copied
+                    .unwrap_or(policy.files_lifetime_after_copied),
+                ratio: rule.ratio.or(policy.ratio),
+                delete_data: match rule.action {
+                    Some(RuleAction::Delete) => true,
+                    Some(RuleAction::Remove) => false,
+                    _ => policy.delete_data,
+                },
+                stop: match rule.action {
+                    Some(RuleAction::Stop) => true,
+                    Some(RuleAction::Delete | RuleAction::Remove) => false,
+                    _ => policy.stop,
+                },
+                stop_lifetime: rule.stop_lifetime.or(policy.stop_lifetime),
+                ..*policy
+            };
+            let mut removal = Removal::for_file(file, &policy, current_time);
+            removal.rule = Some(rule.name.clone());
+            Some(removal)
+        }
+        None => Some(Removal::for_file(file, policy, current_time)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::plan::RemovalReason;
+
+    const RULES: &str = r#"
+        [[rule]]
+        name = "own-releases"
+        action = "keep"
+        [rule.match]
+        label = "release"
+
+        [[rule]]
+        name = "private"
+        lifetime_after_copied = 1000
+        ratio = 2.0
+        [rule.match]
+        tracker = "tracker.org"
+        private = true
+
+        [[rule]]
+        name = "isos"
+        lifetime = 50
+        action = "remove"
+        [rule.match]
+        name = "(?i)\\.iso$"
+        download_dir = "/downloads/isos"
+        max_size = 5000
+    "#;
+
+    fn policy(files_lifetime: u32, files_lifetime_after_copied: u32) -> Policy {
+        Policy {
+            files_lifetime,
+            files_lifetime_after_copied,
+            ..Default::default()
+        }
+    }
+
+    fn file() -> File {
+        File {
+            server_id: 1,
+            added_date: 0,
+            finish_date: Some(100),
+            name: "debian.ISO".to_string(),
+            size: 4000,
+            download_dir: "/downloads/isos/linux".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules(RULES).expect("Failed to parse rules");
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].action, Some(RuleAction::Keep));
+        assert_eq!(rules[1].action, None);
+        assert_eq!(rules[1].lifetime_after_copied, Some(1000));
+        assert_eq!(rules[2].conditions.max_size, Some(5000));
+    }
+
+    #[test]
+    fn test_parse_rules_errors() {
+        assert!(parse_rules("[[rule]]\nname = \"a\"\nunknown = 1").is_err());
+        assert!(parse_rules("[[rule]]\nname = \"a\"\naction = \"explode\"").is_err());
+        assert!(parse_rules("[[rule]]\nname = \"a\"\n[rule.match]\nname = \"(\"").is_err());
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let rules = parse_rules(RULES).expect("Failed to parse rules");
+
+        let iso = file();
+        assert!(!rules[0].matches("default", &iso));
+        assert!(!rules[1].matches("default", &iso));
+        assert!(rules[2].matches("default", &iso));
+
+        let big_iso = File {
+            size: 6000,
+            ..file()
+        };
+        assert!(!rules[2].matches("default", &big_iso));
+
+        let private = File {
+            trackers: vec!["announce.tracker.org".to_string()],
+            private: true,
+            ..file()
+        };
+        assert!(rules[1].matches("default", &private));
+        let public = File {
+            trackers: vec!["nottracker.org".to_string()],
+            private: true,
+            ..file()
+        };
+        assert!(!rules[1].matches("default", &public));
+    }
+
+    #[test]
+    fn test_removal_for() {
+        let rules = parse_rules(RULES).expect("Failed to parse rules");
+
+        // first matching rule wins
+        let release = File {
+            labels: vec!["release".to_string()],
+            ..file()
+        };
+        assert!(removal_for(&rules, "default", &release, &policy(10, 10), 200).is_none());
+
+        let removal = removal_for(&rules, "default", &file(), &policy(1000, 1000), 200)
+            .expect("File should be removed");
+        assert_eq!(removal.rule, Some("isos".to_string()));
+        assert_eq!(removal.due_date, 50);
+        assert!(!removal.delete_data);
+
+        let other = File {
+            name: "movie.mkv".to_string(),
+            ..file()
+        };
+        let removal = removal_for(&rules, "default", &other, &policy(1000, 10), 200)
+            .expect("File should be removed");
+        assert_eq!(removal.rule, None);
+        assert_eq!(removal.due_date, 110);
+
+        // the ratio of the rule removes the file before its lifetime
+        let seeded = File {
+            trackers: vec!["tracker.org".to_string()],
+            private: true,
+            upload_ratio: 2.5,
+            ..file()
+        };
+        let removal = removal_for(&rules, "default", &seeded, &policy(1000, 10), 200)
+            .expect("File should be removed");
+        assert_eq!(removal.reason, RemovalReason::Ratio);
+        assert!(removal.is_due(200));
+    }
+
+    #[test]
+    fn test_removal_for_keep_data() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            name = "short"
+            lifetime_after_copied = 10
+
+            [[rule]]
+            name = "junk"
+            action = "delete"
+            [rule.match]
+            label = "junk"
+            "#,
+        )
+        .expect("Failed to parse rules");
+        let keep_data = Policy {
+            delete_data: false,
+            ..policy(1000, 1000)
+        };
+
+        // a rule without action keeps the data like the instance
+        let removal = removal_for(&rules, "default", &file(), &keep_data, 200)
+            .expect("File should be removed");
+        assert_eq!(removal.rule, Some("short".to_string()));
+        assert!(!removal.delete_data);
+
+        // only an explicit delete action deletes it
+        let rules = vec![rules[1].clone()];
+        let junk = File {
+            labels: vec!["junk".to_string()],
+            ..file()
+        };
+        let removal =
+            removal_for(&rules, "default", &junk, &keep_data, 200).expect("File should be removed");
+        assert!(removal.delete_data);
+    }
+
+    #[test]
+    fn test_removal_for_stop() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            name = "seed-longer"
+            action = "stop"
+            stop_lifetime = 500
+            "#,
+        )
+        .expect("Failed to parse rules");
+        let file = File {
+            finish_date: Some(100),
+            ..Default::default()
+        };
+
+        // stopped once the lifetime after copied expires
+        let removal = removal_for(&rules, "default", &file, &policy(10000, 10), 200)
+            .expect("File should be stopped");
+        assert!(removal.stop);
+        assert_eq!(removal.mode(), "stop");
+        assert_eq!(removal.due_date, 110);
+        assert_eq!(removal.expiry_date(), Some(110));
+        assert!(removal.is_due(200));
+        assert!(!removal.is_removal_due(200));
+
+        // not due anymore once stopped, it expires with the stop lifetime
+        let stopped = File {
+            stopped_date: Some(200),
+            ..file.clone()
+        };
+        let removal = removal_for(&rules, "default", &stopped, &policy(10000, 10), 300)
+            .expect("File should be stopped");
+        assert!(!removal.is_due(300));
+        assert_eq!(removal.expiry_date(), Some(600));
+
+        // removed with its data once the stop lifetime expires
+        let delete_data = Policy {
+            delete_data: true,
+            ..policy(10000, 10)
+        };
+        let removal = removal_for(&rules, "default", &file, &delete_data, 700)
+            .expect("File should be removed");
+        assert!(!removal.stop);
+        assert_eq!(removal.mode(), "delete_data");
+        assert_eq!(removal.due_date, 600);
+        assert!(removal.is_removal_due(700));
+
+        // stopped torrents are never removed without a stop lifetime
+        let rules = parse_rules("[[rule]]\nname = \"stop\"\naction = \"stop\"")
+            .expect("Failed to parse rules");
+        let removal = removal_for(&rules, "default", &stopped, &policy(10000, 10), 1_000_000)
+            .expect("File should be stopped");
+        assert!(removal.stop);
+        assert_eq!(removal.expiry_date(), None);
+    }
+
+    #[test]
+    fn test_removal_action_overrides_stop() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            name = "delete-isos"
+            action = "delete"
+            match = { name = "(?i)iso" }
+            "#,
+        )
+        .expect("Failed to parse rules");
+        let stop = Policy {
+            stop: true,
+            stop_lifetime: Some(500),
+            ..policy(10000, 10)
+        };
+
+        // removed right away instead of being stopped by the instance
+        let removal =
+            removal_for(&rules, "default", &file(), &stop, 200).expect("File should be removed");
+        assert!(!removal.stop);
+        assert!(removal.is_removal_due(200));
+
+        // torrents without a matching rule are still stopped
+        let other = File {
+            name: "movie.mkv".to_string(),
+            ..file()
+        };
+        let removal =
+            removal_for(&rules, "default", &other, &stop, 200).expect("File should be stopped");
+        assert!(removal.stop);
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use crate::logic::database::models::File;
+use crate::logic::metrics::Metrics;
+use crate::logic::plan::Removal;
+
+/// Maximum size of a request, the API only serves small GET requests.
+const MAX_REQUEST_SIZE: usize = 8192;
+/// Time in seconds a client has to send its request and read the response.
+const CONNECTION_TIMEOUT_SECS: u64 = 10;
+
+/// Scan intervals without a successful scan after which the service is unhealthy.
+const HEALTH_SCAN_INTERVALS: i64 = 3;
+/// Time in seconds added to the health threshold, scans of slow clients take a while.
+const HEALTH_GRACE_PERIOD: i64 = 60;
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A tracked torrent with when and why it is going to be removed.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedTorrent {
+    pub instance: String,
+    pub hash: String,
+    pub name: String,
+    pub size: i64,
+    pub download_dir: String,
+    pub added_date: i64,
+    pub finish_date: Option<i64>,
+    pub stopped_date: Option<i64>,
+    /// Protected torrents are never removed, they have no expiry.
+    pub protected: bool,
+    pub reason: Option<String>,
+    pub rule: Option<String>,
+    pub mode: Option<String>,
+    pub expiry_date: Option<i64>,
+}
+
+impl TrackedTorrent {
+    pub fn from_removal(instance: &str, removal: &Removal) -> Self {
+        TrackedTorrent {
+            reason: Some(removal.reason.as_str().to_string()),
+            rule: removal.rule.clone(),
+            mode: Some(removal.mode().to_string()),
+            expiry_date: removal.expiry_date(),
+            ..Self::from_file(instance, &removal.file, false)
+        }
+    }
+
+    pub fn protected(instance: &str, file: &File) -> Self {
+        Self::from_file(instance, file, true)
+    }
+
+    fn from_file(instance: &str, file: &File, protected: bool) -> Self {
+        TrackedTorrent {
+            instance: instance.to_string(),
+            hash: file.hash.clone(),
+            name: file.name.clone(),
+            size: file.size,
+            download_dir: file.download_dir.clone(),
+            added_date: file.added_date,
+            finish_date: file.finish_date,
+            stopped_date: file.stopped_date,
+            protected,
+            reason: None,
+            rule: None,
+            mode: None,
+            expiry_date: None,
+        }
+    }
+}
+
+/// Outcome of the last scan of an instance.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanResult {
+    pub instance: String,
+    pub started_date: i64,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Torrents tracked after the scan.
+    pub tracked: usize,
+    /// Torrents whose removal, or stop, was due during the scan.
+    pub due: usize,
+    /// Torrents whose removal is not due yet.
+    pub pending: usize,
+}
+
+/// Health of an instance, computed from its scans.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceHealth {
+    pub instance: String,
+    pub last_success_date: Option<i64>,
+    pub last_error: Option<String>,
+    /// No scan succeeded for a few scan intervals, the monitor may be hung.
+    pub stale: bool,
+    /// The last scan reached the torrent client.
+    pub reachable: bool,
+}
+
+/// Health of the service, `live` fails when the scans stopped and `ready` also fails until the
+/// first scan succeeded or while a torrent client is unreachable.
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    pub live: bool,
+    pub ready: bool,
+    pub instances: Vec<InstanceHealth>,
+}
+
+/// State of the service exposed by the status API, updated after every scan.
+#[derive(Debug, Default)]
+pub struct Status {
+    started_date: i64,
+    scan_interval: u32,
+    last_success: BTreeMap<String, i64>,
+    torrents: BTreeMap<String, Vec<TrackedTorrent>>,
+    scans: BTreeMap<String, ScanResult>,
+    /// Configuration of the service, secrets must be redacted by the caller.
+    config: serde_json::Value,
+    metrics: Metrics,
+}
+
+impl Status {
+    /// Register the instances when the monitor starts, they are unhealthy until scanned.
+    pub fn start(&mut self, instances: &[String], scan_interval: u32, current_time: i64) {
+        self.started_date = current_time;
+        self.scan_interval = scan_interval;
+        for instance in instances {
+            self.scans
+                .entry(instance.clone())
+                .or_insert_with(|| ScanResult {
+                    instance: instance.clone(),
+                    ..Default::default()
+                });
+        }
+    }
+
+    /// Change the scan interval the health is computed with, when the settings are reloaded.
+    pub fn set_scan_interval(&mut self, scan_interval: u32) {
+        self.scan_interval = scan_interval;
+    }
+
+    pub fn set_torrents(&mut self, instance: &str, torrents: Vec<TrackedTorrent>) {
+        self.torrents.insert(instance.to_string(), torrents);
+    }
+
+    /// Keep the last scan of the instance and count it in the metrics.
+    pub fn set_scan(&mut self, scan: ScanResult) {
+        self.metrics.record_scan(&scan);
+        if scan.success {
+            self.last_success
+                .insert(scan.instance.clone(), scan.started_date);
+        }
+        self.scans.insert(scan.instance.clone(), scan);
+    }
+
+    /// The last scan of the instance failed.
+    pub fn scan_failed(&self, instance: &str) -> bool {
+        self.scans
+            .get(instance)
+            .is_some_and(|scan| scan.error.is_some())
+    }
+
+    pub fn health(&self, current_time: i64) -> Health {
+        let threshold =
+            HEALTH_SCAN_INTERVALS * self.scan_interval.max(1) as i64 + HEALTH_GRACE_PERIOD;
+        let instances: Vec<InstanceHealth> = self
+            .scans
+            .values()
+            .map(|scan| {
+                let last_success_date = self.last_success.get(&scan.instance).copied();
+                InstanceHealth {
+                    instance: scan.instance.clone(),
+                    last_success_date,
+                    last_error: scan.error.clone(),
+                    stale: current_time - last_success_date.unwrap_or(self.started_date)
+                        > threshold,
+                    reachable: scan.error.is_none(),
+                }
+            })
+            .collect();
+
+        Health {
+            live: instances.iter().all(|instance| !instance.stale),
+            ready: instances.iter().all(|instance| {
+                instance.last_success_date.is_some() && !instance.stale && instance.reachable
+            }),
+            instances,
+        }
+    }
+
+    pub fn set_config(&mut self, config: serde_json::Value) {
+        self.config = config;
+    }
+
+    pub fn torrents(&self) -> Vec<&TrackedTorrent> {
+        self.torrents.values().flatten().collect()
+    }
+
+    pub fn scans(&self) -> Vec<&ScanResult> {
+        self.scans.values().collect()
+    }
+
+    pub fn config(&self) -> &serde_json::Value {
+        &self.config
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+}
+
+/// Serve the status API on the listener until the task is aborted.
+pub async fn serve(listener: TcpListener, status: Arc<Mutex<Status>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let status = status.clone();
+                tokio::spawn(async move {
+                    let timeout = std::time::Duration::from_secs(CONNECTION_TIMEOUT_SECS);
+                    match tokio::time::timeout(timeout, handle_connection(stream, &status)).await {
+                        Ok(Err(e)) => println!("Status API error: {}", e),
+                        Err(_) => println!("Status API error: connection timed out"),
+                        Ok(Ok(())) => {}
+                    }
+                });
+            }
+            Err(e) => {
+                println!("Status API failed to accept a connection: {}", e);
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, status: &Mutex<Status>) -> Result<(), String> {
+    let mut request = vec![];
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if request.len() > MAX_REQUEST_SIZE {
+            return write_error(&mut stream, 413, "request too large").await;
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    // the query string is ignored
+    let path = request_line
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    if method != "GET" {
+        return write_error(&mut stream, 405, "method not allowed").await;
+    }
+
+    // the body is built under the lock, which is released before writing to a slow client
+    let (code, content_type, body) = {
+        let status = status.lock().await;
+        let health = || status.health(current_timestamp());
+        match path {
+            "/health/live" => {
+                let health = health();
+                let code = if health.live { 200 } else { 503 };
+                (code, JSON_CONTENT_TYPE, serde_json::to_string(&health))
+            }
+            "/health" | "/health/ready" => {
+                let health = health();
+                let code = if health.ready { 200 } else { 503 };
+                (code, JSON_CONTENT_TYPE, serde_json::to_string(&health))
+            }
+            "/torrents" => (
+                200,
+                JSON_CONTENT_TYPE,
+                serde_json::to_string(&status.torrents()),
+            ),
+            "/scans" => (
+                200,
+                JSON_CONTENT_TYPE,
+                serde_json::to_string(&status.scans()),
+            ),
+            "/config" => (
+                200,
+                JSON_CONTENT_TYPE,
+                serde_json::to_string(status.config()),
+            ),
+            "/metrics" => (200, METRICS_CONTENT_TYPE, Ok(status.metrics().render())),
+            _ => (
+                404,
+                JSON_CONTENT_TYPE,
+                Ok(serde_json::json!({ "error": "not found" }).to_string()),
+            ),
+        }
+    };
+    let body = body.map_err(|e| e.to_string())?;
+    write_response(&mut stream, code, content_type, &body).await
+}
+
+async fn write_error(stream: &mut TcpStream, code: u16, error: &str) -> Result<(), String> {
+    let body = serde_json::json!({ "error": error }).to_string();
+    write_response(stream, code, JSON_CONTENT_TYPE, &body).await
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    code: u16,
+    content_type: &str,
+    body: &str,
+) -> Result<(), String> {
+    let reason = match code {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    stream.shutdown().await.map_err(|e| e.to_string())
+}
+
+fn current_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(instance: &str, started_date: i64, error: Option<&str>) -> ScanResult {
+        ScanResult {
+            instance: instance.to_string(),
+            started_date,
+            success: error.is_none(),
+            error: error.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let mut status = Status::default();
+        status.start(&["box1".to_string(), "box2".to_string()], 60, 1000);
+
+        // not scanned yet, alive but not ready
+        let health = status.health(1010);
+        assert!(health.live);
+        assert!(!health.ready);
+
+        status.set_scan(scan("box1", 1060, None));
+        status.set_scan(scan("box2", 1060, None));
+        assert!(status.health(1070).ready);
+
+        // the torrent client of box2 is unreachable
+        status.set_scan(scan("box2", 1120, Some("Connection refused")));
+        let health = status.health(1130);
+        assert!(health.live);
+        assert!(!health.ready);
+        assert_eq!(
+            health.instances[1].last_error.as_deref(),
+            Some("Connection refused")
+        );
+        assert!(!health.instances[1].reachable);
+
+        // no successful scan for more than 3 intervals and the grace period
+        let health = status.health(1060 + 3 * 60 + 61);
+        assert!(!health.live);
+        assert!(health.instances.iter().all(|instance| instance.stale));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::logic::database::models::TrashEntry;
+
+/// Where expired torrents are moved before their data is deleted for good.
+#[derive(Debug, Clone, Default)]
+pub struct TrashPolicy {
+    /// Trash directory, it must have the same path for the torrent client and the service.
+    pub directory: String,
+    /// Time in seconds the data stays in the trash.
+    pub grace_period: u32,
+    /// Maximum size in bytes of the trash, the oldest entries are purged first.
+    pub max_size: Option<i64>,
+}
+
+impl TrashPolicy {
+    /// Directory the data of a torrent is moved into, unique for every trashed torrent.
+    pub fn location(&self, server_id: i32, current_time: i64) -> String {
+        format!(
+            "{}/{}-{}",
+            self.directory.trim_end_matches('/'),
+            current_time,
+            server_id
+        )
+    }
+
+    /// Entries that must be purged, the given entries are sorted by trashed date.
+    pub fn entries_to_purge<'a>(
+        &self,
+        entries: &'a [TrashEntry],
+        current_time: i64,
+    ) -> Vec<&'a TrashEntry> {
+        let mut size: i64 = entries.iter().map(|entry| entry.size).sum();
+        let mut purged = vec![];
+        for entry in entries {
+            let expired = current_time >= entry.trashed_date + self.grace_period as i64;
+            let over_size = self.max_size.is_some_and(|max_size| size > max_size);
+            if expired || over_size {
+                size -= entry.size;
+                purged.push(entry);
+            }
+        }
+        purged
+    }
+}
+
+/// Delete the data of a trash entry from the disk.
+pub fn purge(entry: &TrashEntry) -> Result<(), String> {
+    let path = std::path::Path::new(&entry.path);
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+
+    match result {
+        Ok(_) => Ok(()),
+        // the data was already removed by hand
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to purge {}: {}", entry.path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: i32, size: i64, trashed_date: i64) -> TrashEntry {
+        TrashEntry {
+            id,
+            size,
+            trashed_date,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_location() {
+        let policy = TrashPolicy {
+            directory: "/downloads/.trash/".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(policy.location(3, 100), "/downloads/.trash/100-3");
+    }
+
+    #[test]
+    fn test_entries_to_purge() {
+        let entries = vec![entry(1, 100, 10), entry(2, 200, 50), entry(3, 300, 90)];
+
+        let policy = TrashPolicy {
+            grace_period: 50,
+            ..Default::default()
+        };
+        let purged = policy.entries_to_purge(&entries, 100);
+        assert_eq!(
+            purged.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        // the oldest entries are purged until the trash fits
+        let policy = TrashPolicy {
+            grace_period: 1000,
+            max_size: Some(400),
+            ..Default::default()
+        };
+        let purged = policy.entries_to_purge(&entries, 100);
+        assert_eq!(
+            purged.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+}
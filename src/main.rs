@@ -1,25 +1,249 @@
 use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use fp::Monitor;
+use fp::logic::api::{TorrentClient, new_client};
+use fp::logic::email::Mailer;
+use fp::{DEFAULT_INSTANCE, Instance, InstanceSettings, Monitor, Reload};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{Mutex, mpsc};
 
 mod args;
+mod commands;
+mod config;
+mod reload;
+
+/// Default time in seconds the scan in progress has to finish when the service is stopped,
+/// below the 10 seconds `docker stop` waits before killing the process.
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u32 = 8;
+
+const DEFAULT_CLIENT: &str = "transmission";
 
 #[tokio::main]
 async fn main() {
-    let args_values = args::Args::new(env::args().collect());
+    let args_values = match args::Args::new(env::args().collect()) {
+        Ok(args_values) => args_values,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if args_values.validate() {
-        let mut monitor = Monitor::new(
-            args_values.monitoring_url.unwrap().as_str(),
-            args_values.database_path,
-            args_values.scan_interval,
-            args_values.file_lifetime,
-            args_values.file_lifetime_after_copied,
-            args_values.username.unwrap().as_str(),
-            args_values.password.unwrap().as_str(),
+    if !args_values.command.is_empty() {
+        if let Err(e) = commands::run(&args_values).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if args_values.validate() {
+        let instances = settings(&args_values).and_then(|settings| {
+            let instances = settings
+                .instances
+                .iter()
+                .cloned()
+                .zip(clients(&args_values))
+                .map(|(instance, client)| Ok(Instance::with_settings(instance, client.connect()?)))
+                .collect::<Result<Vec<Instance>, String>>()?;
+            Ok((settings, instances))
+        });
+        let (settings, instances) = match instances {
+            Ok(instances) => instances,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let mut monitor = Monitor::with_instances(
+            instances,
+            args_values.database_path.clone(),
+            settings.scan_interval,
+        );
+        monitor.set_dry_run(args_values.dry_run);
+        monitor.set_rules(settings.rules);
+        monitor.set_webhooks(settings.webhooks);
+        monitor.set_mailer(settings.mailer);
+        monitor.set_status_address(args_values.status_address.clone());
+        monitor.status().lock().await.set_config(settings.config);
+
+        let shutdown_timeout = args_values
+            .shutdown_timeout
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+
+        let (sender, receiver) = mpsc::channel(1);
+        monitor.set_reloads(Some(receiver));
+        tokio::spawn(async move { reload::watch(sender, &args_values).await });
+
+        let removals = monitor.removals();
+        let stop_signal = Arc::new(Mutex::new(AtomicBool::new(false)));
+        let monitor_stop_signal = stop_signal.clone();
+        let mut monitor_task = tokio::spawn(async move {
+            monitor.run(Some(monitor_stop_signal)).await;
+        });
+
+        let signal_name = tokio::select! {
+            signal_name = shutdown_signal() => signal_name,
+            // the monitor only stops by itself if it panicked
+            _ = &mut monitor_task => std::process::exit(1),
+        };
+        println!(
+            "{} received, stopping once the scan in progress is finished",
+            signal_name
         );
-        monitor.run(None).await;
+        stop_signal.lock().await.store(true, Ordering::SeqCst);
+        if tokio::time::timeout(
+            Duration::from_secs(shutdown_timeout as u64),
+            &mut monitor_task,
+        )
+        .await
+        .is_err()
+        {
+            // the database connection is closed when the aborted monitor is dropped
+            println!(
+                "The scan did not finish within {} seconds, aborting it",
+                shutdown_timeout
+            );
+            // a removal in progress is recorded in the database before the monitor is aborted
+            let _removals = removals.write().await;
+            monitor_task.abort();
+            let _ = monitor_task.await;
+        }
+        println!("Stopped");
     } else {
         eprintln!("Invalid arguments provided. Please check help with -h.");
     }
 }
+
+/// Wait for SIGTERM, e.g. from `docker stop`, or SIGINT and return its name.
+async fn shutdown_signal() -> &'static str {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            eprintln!("Failed to listen to SIGTERM: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+    }
+}
+
+/// Torrent client of an instance. The clients are only connected at startup, changing them
+/// requires a restart.
+#[derive(Debug, PartialEq)]
+struct ClientSettings {
+    name: String,
+    client: String,
+    username: String,
+    password: String,
+    monitoring_url: String,
+}
+
+impl ClientSettings {
+    fn connect(&self) -> Result<Box<dyn TorrentClient>, String> {
+        new_client(
+            &self.client,
+            self.username.clone(),
+            self.password.clone(),
+            &self.monitoring_url,
+        )
+    }
+}
+
+/// The torrent clients of the instances, in the order of `settings`.
+fn clients(args_values: &args::Args) -> Vec<ClientSettings> {
+    let mut clients = vec![];
+    if let Some(monitoring_url) = &args_values.monitoring_url {
+        clients.push(ClientSettings {
+            name: DEFAULT_INSTANCE.to_string(),
+            client: args_values
+                .client
+                .clone()
+                .unwrap_or(DEFAULT_CLIENT.to_string()),
+            username: args_values.username.clone().unwrap_or_default(),
+            password: args_values.password.clone().unwrap_or_default(),
+            monitoring_url: monitoring_url.clone(),
+        });
+    }
+    for instance_args in &args_values.instances {
+        clients.push(ClientSettings {
+            name: instance_args.name.clone(),
+            client: instance_args
+                .client
+                .clone()
+                .or(args_values.client.clone())
+                .unwrap_or(DEFAULT_CLIENT.to_string()),
+            username: instance_args
+                .username
+                .clone()
+                .or(args_values.username.clone())
+                .unwrap_or_default(),
+            password: instance_args
+                .password
+                .clone()
+                .or(args_values.password.clone())
+                .unwrap_or_default(),
+            monitoring_url: instance_args.monitoring_url.clone().unwrap_or_default(),
+        });
+    }
+    clients
+}
+
+/// Settings only read at startup: the torrent clients, the database and the status server.
+/// Reloads changing them are rejected.
+fn startup_settings(
+    args_values: &args::Args,
+) -> (Vec<ClientSettings>, Option<String>, Option<String>) {
+    (
+        clients(args_values),
+        args_values.database_path.clone(),
+        args_values.status_address.clone(),
+    )
+}
+
+/// Build the settings which can be reloaded while the monitor runs.
+fn settings(args_values: &args::Args) -> Result<Reload, String> {
+    let mut instances: Vec<InstanceSettings> = vec![];
+    if args_values.monitoring_url.is_some() {
+        let mut instance = InstanceSettings::new(
+            DEFAULT_INSTANCE,
+            args_values.file_lifetime,
+            args_values.file_lifetime_after_copied,
+        );
+        instance.set_ratio(args_values.ratio, args_values.client_ratio_limit);
+        instance.set_free_space(args_values.free_space_policy());
+        instance.set_trash(args_values.trash_policy());
+        instance.set_delete_data(!args_values.keep_data);
+        instance.set_stop(args_values.stop_lifetime);
+        instances.push(instance);
+    }
+    for instance_args in &args_values.instances {
+        let mut instance = InstanceSettings::new(
+            instance_args.name.as_str(),
+            instance_args.file_lifetime.or(args_values.file_lifetime),
+            instance_args
+                .file_lifetime_after_copied
+                .or(args_values.file_lifetime_after_copied),
+        );
+        instance.set_ratio(
+            instance_args.ratio.or(args_values.ratio),
+            instance_args
+                .client_ratio_limit
+                .unwrap_or(args_values.client_ratio_limit),
+        );
+        instance.set_free_space(args_values.free_space_policy());
+        instance.set_trash(args_values.trash_policy());
+        instance.set_delete_data(!instance_args.keep_data.unwrap_or(args_values.keep_data));
+        instance.set_stop(instance_args.stop_lifetime.or(args_values.stop_lifetime));
+        instances.push(instance);
+    }
+
+    Ok(Reload {
+        scan_interval: args_values.scan_interval,
+        instances,
+        rules: args_values.rules()?,
+        webhooks: args_values.webhook().into_iter().collect(),
+        mailer: args_values.email_settings().map(Mailer::new).transpose()?,
+        config: args_values.redacted_config(),
+    })
+}
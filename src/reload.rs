@@ -0,0 +1,98 @@
+use std::env;
+use std::time::{Duration, SystemTime};
+
+use fp::Reload;
+use tokio::signal::unix::{Signal, SignalKind, signal};
+use tokio::sync::mpsc::Sender;
+
+use crate::args::Args;
+use crate::{settings, startup_settings};
+
+/// Time between two checks of the modification dates of the config and rules files.
+const WATCH_INTERVAL_SECS: u64 = 5;
+
+/// Send the settings to the monitor on SIGHUP, or when the config or rules file changes.
+/// Invalid settings, and the ones changing what is only read at startup, are reported and never
+/// sent, the monitor keeps the current ones.
+pub async fn watch(sender: Sender<Reload>, args: &Args) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => Some(hangup),
+        Err(e) => {
+            println!("Failed to listen to SIGHUP: {}", e);
+            None
+        }
+    };
+    let startup = startup_settings(args);
+    let mut files = watched_files(args);
+    let mut modified = modified_dates(&files);
+
+    loop {
+        let reason = tokio::select! {
+            _ = received(&mut hangup) => "SIGHUP received",
+            _ = tokio::time::sleep(Duration::from_secs(WATCH_INTERVAL_SECS)) => {
+                let dates = modified_dates(&files);
+                if dates == modified {
+                    continue;
+                }
+                modified = dates;
+                "Configuration file changed"
+            }
+        };
+        println!("{}, reloading the configuration", reason);
+
+        let reload = Args::new(env::args().collect()).and_then(|args| {
+            if !args.validate() {
+                return Err("Invalid arguments provided".to_string());
+            }
+            if startup_settings(&args) != startup {
+                return Err(
+                    "the torrent clients, database path and status address require a restart"
+                        .to_string(),
+                );
+            }
+            let reload = settings(&args)?;
+            Ok((args, reload))
+        });
+        match reload {
+            Ok((args, reload)) => {
+                files = watched_files(&args);
+                modified = modified_dates(&files);
+                if sender.send(reload).await.is_err() {
+                    // the monitor stopped
+                    break;
+                }
+            }
+            Err(e) => {
+                println!("Invalid configuration, keeping the current one: {}", e);
+            }
+        }
+    }
+}
+
+async fn received(hangup: &mut Option<Signal>) {
+    match hangup {
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn watched_files(args: &Args) -> Vec<String> {
+    args.config_path
+        .iter()
+        .chain(args.rules_path.iter())
+        .cloned()
+        .collect()
+}
+
+fn modified_dates(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            std::fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
 use base64::prelude::*;
-use fp::logic::api::Api;
+use fp::logic::api::transmission::TransmissionApi;
+use fp::logic::api::{TorrentClient, new_client};
 use mockito::Matcher;
 
 #[tokio::test]
@@ -13,19 +14,21 @@ async fn test_api_list_files() {
         .match_header("authorization", Matcher::Exact(format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", username, password)))))
         .with_status(200)
         .with_header("content-type", "application/json; charset=UTF-8")
-        .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"addedDate\": 1763580763, \"isFinished\": false, \"percentDone\": 0.5} ] }, \"result\": \"success\" }")
+        .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"hashString\": \"C12FE1C06BBA254A9DC9F519B335AA7C1367A88A\", \"addedDate\": 1763580763, \"isFinished\": false, \"percentDone\": 0.5} ] }, \"result\": \"success\" }")
         .create();
 
-    let mut api = Api::new(
+    let mut api = TransmissionApi::new(
         username.to_string(),
         password.to_string(),
         format!("{}/transmission/rpc", server.url()).as_str(),
-    );
+    )
+    .unwrap();
 
     match api.fetch_files().await {
         Ok(files) => {
             assert_eq!(files[0].id, 0);
             assert_eq!(files[0].server_id, 1);
+            assert_eq!(files[0].hash, "c12fe1c06bba254a9dc9f519b335aa7c1367a88a");
             assert_eq!(files[0].added_date, 1763580763);
             assert_eq!(files[0].finish_date, None);
         }
@@ -44,14 +47,15 @@ async fn test_api_list_files_with_finish() {
         .with_status(200)
         .match_header("authorization", Matcher::Exact(format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", username, password)))))
         .with_header("content-type", "application/json; charset=UTF-8")
-        .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"addedDate\": 1763580763, \"isFinished\": true, \"percentDone\": 1} ] }, \"result\": \"success\" }")
+        .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"addedDate\": 1763580763, \"isFinished\": true, \"percentDone\": 1, \"uploadRatio\": 1.5, \"seedRatioLimit\": 2, \"seedRatioMode\": 1} ] }, \"result\": \"success\" }")
         .create();
 
-    let mut api = Api::new(
+    let mut api = TransmissionApi::new(
         username.to_string(),
         password.to_string(),
         format!("{}/transmission/rpc", server.url()).as_str(),
-    );
+    )
+    .unwrap();
 
     match api.fetch_files().await {
         Ok(files) => {
@@ -59,6 +63,9 @@ async fn test_api_list_files_with_finish() {
             assert_eq!(files[0].server_id, 1);
             assert_eq!(files[0].added_date, 1763580763);
             assert!(files[0].finish_date.is_some());
+            assert_eq!(files[0].upload_ratio, 1.5);
+            assert_eq!(files[0].seed_ratio_limit, 2.0);
+            assert_eq!(files[0].seed_ratio_mode, 1);
         }
         Err(e) => panic!("API fetch_files failed: {}", e),
     }
@@ -78,11 +85,12 @@ async fn test_api_list_files_with_finish_by_percent() {
         .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"addedDate\": 1763580763, \"isFinished\": false, \"percentDone\": 1} ] }, \"result\": \"success\" }")
         .create();
 
-    let mut api = Api::new(
+    let mut api = TransmissionApi::new(
         username.to_string(),
         password.to_string(),
         format!("{}/transmission/rpc", server.url()).as_str(),
-    );
+    )
+    .unwrap();
 
     match api.fetch_files().await {
         Ok(files) => {
@@ -96,12 +104,56 @@ async fn test_api_list_files_with_finish_by_percent() {
 }
 
 #[tokio::test]
-async fn test_api_delete_file() {
+async fn test_api_list_files_with_client_dates() {
     let mut server = mockito::Server::new_async().await;
     let username = "test_user";
     let password = "test_password";
 
     server
+        .mock("POST", "/transmission/rpc")
+        .with_status(200)
+        .match_header("authorization", Matcher::Exact(format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", username, password)))))
+        .with_header("content-type", "application/json; charset=UTF-8")
+        .with_body("{ \"arguments\": { \"torrents\": [ {\"id\": 1, \"addedDate\": 1763580763, \"isFinished\": true, \"percentDone\": 1, \"doneDate\": 1763581000, \"secondsSeeding\": 600, \"activityDate\": 1763581500}, {\"id\": 2, \"addedDate\": 1763580763, \"isFinished\": true, \"percentDone\": 1, \"doneDate\": 0, \"secondsSeeding\": 600, \"activityDate\": 0}, {\"id\": 3, \"addedDate\": 1763580763, \"isFinished\": true, \"percentDone\": 1, \"doneDate\": 0, \"secondsSeeding\": 0, \"activityDate\": 1763581500} ] }, \"result\": \"success\" }")
+        .create();
+
+    let mut api = TransmissionApi::new(
+        username.to_string(),
+        password.to_string(),
+        format!("{}/transmission/rpc", server.url()).as_str(),
+    )
+    .unwrap();
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    match api.fetch_files().await {
+        Ok(files) => {
+            assert_eq!(files[0].finish_date, Some(1763581000));
+            assert!(!files[0].finish_date_estimated);
+            assert_eq!(files[0].seconds_seeding, 600);
+            assert_eq!(files[0].activity_date, Some(1763581500));
+            // without done date the seeding time is used, it leaves out the pauses
+            let finish_date = files[1].finish_date.expect("File should be finished");
+            assert!((now - 600..=now - 599).contains(&finish_date));
+            assert!(files[1].finish_date_estimated);
+            assert_eq!(files[1].activity_date, None);
+            // without seeding time the last activity is the estimate
+            assert_eq!(files[2].finish_date, Some(1763581500));
+            assert!(files[2].finish_date_estimated);
+        }
+        Err(e) => panic!("API fetch_files failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_api_delete_file() {
+    let mut server = mockito::Server::new_async().await;
+    let username = "test_user";
+    let password = "test_password";
+
+    let mock = server
         .mock("POST", "/transmission/rpc")
         .with_status(200)
         .match_header(
@@ -111,18 +163,69 @@ async fn test_api_delete_file() {
                 BASE64_STANDARD.encode(format!("{}:{}", username, password))
             )),
         )
+        .match_body(Matcher::PartialJsonString(
+            "{ \"method\": \"torrent-remove\", \"arguments\": { \"ids\": [\"aaa\", \"bbb\"] } }"
+                .to_string(),
+        ))
         .with_header("content-type", "application/json; charset=UTF-8")
         .with_body("{ \"arguments\": { }, \"result\": \"success\" }")
         .create();
 
-    let mut api = Api::new(
+    let mut api = TransmissionApi::new(
         username.to_string(),
         password.to_string(),
         format!("{}/transmission/rpc", server.url()).as_str(),
-    );
-
-    match api.delete_file(&vec![1, 2, 3]).await {
-        Ok(_) => {}
+    )
+    .unwrap();
+
+    match api
+        .delete_file(&["aaa".to_string(), "bbb".to_string()], true)
+        .await
+    {
+        Ok(_) => mock.assert(),
         Err(e) => panic!("API delete_file failed: {}", e),
     }
 }
+
+#[tokio::test]
+async fn test_api_stop_file() {
+    let mut server = mockito::Server::new_async().await;
+    let username = "test_user";
+    let password = "test_password";
+
+    let mock = server
+        .mock("POST", "/transmission/rpc")
+        .with_status(200)
+        .match_body(Matcher::PartialJsonString(
+            "{ \"method\": \"torrent-stop\", \"arguments\": { \"ids\": [\"aaa\"] } }".to_string(),
+        ))
+        .with_header("content-type", "application/json; charset=UTF-8")
+        .with_body("{ \"arguments\": { }, \"result\": \"success\" }")
+        .create();
+
+    let mut api = TransmissionApi::new(
+        username.to_string(),
+        password.to_string(),
+        format!("{}/transmission/rpc", server.url()).as_str(),
+    )
+    .unwrap();
+
+    match api.stop_file(&["aaa".to_string()]).await {
+        Ok(_) => mock.assert(),
+        Err(e) => panic!("API stop_file failed: {}", e),
+    }
+}
+
+#[test]
+fn test_api_invalid_url() {
+    for client in ["transmission", "qbittorrent", "deluge", "rtorrent"] {
+        let error = new_client(client, String::new(), String::new(), "not a url")
+            .err()
+            .unwrap();
+        assert!(
+            error.starts_with("Invalid API URL 'not a url'"),
+            "{}",
+            error
+        );
+    }
+}
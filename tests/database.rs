@@ -1,5 +1,8 @@
 use fp::logic::database::Database;
-use fp::logic::database::models::{File, MigrationVersion};
+use fp::logic::database::models::{
+    File, HistoryEntry, MigrationVersion, PlanEntry, Protection, TrashEntry,
+};
+use fp::logic::history::HistoryFilter;
 use rusqlite::fallible_streaming_iterator::FallibleStreamingIterator;
 
 async fn is_migration_version_table_available(db: &Database) -> bool {
@@ -43,7 +46,7 @@ async fn test_create_database() {
         .unwrap();
 
     // validate the number of versions (update this if new migrations are added)
-    assert_eq!(versions.len(), 1);
+    assert_eq!(versions.len(), 14);
 
     // Check initial migration version
     let initial_version = 1;
@@ -97,6 +100,7 @@ async fn get_file_by_id(db: &Database, file_id: i32) -> Option<File> {
                 server_id: row.get(1)?,
                 added_date: row.get(2)?,
                 finish_date: row.get(3)?,
+                ..Default::default()
             })
         })
         .expect("Failed to query file table")
@@ -126,6 +130,7 @@ async fn test_create_file() {
         server_id: 1,
         added_date: 1625079600,
         finish_date: None,
+        ..Default::default()
     };
     let id1 = db.create_or_update_file(file.clone()).await;
 
@@ -140,6 +145,7 @@ async fn test_create_file() {
         server_id: 1,
         added_date: 1625079601,
         finish_date: Some(1625083200),
+        ..Default::default()
     };
     let id2 = db.create_or_update_file(updated_file.clone()).await;
     assert_eq!(id1, id2, "File IDs should be the same after update");
@@ -154,6 +160,7 @@ async fn test_create_file() {
         server_id: 1,
         added_date: 1625079601,
         finish_date: Some(1625086800),
+        ..Default::default()
     };
     let id3 = db
         .create_or_update_file(finish_date_only_update.clone())
@@ -178,25 +185,28 @@ async fn test_remove_no_matching_files_ids() {
         server_id: 4,
         added_date: 1625079600,
         finish_date: None,
+        ..Default::default()
     };
     let file2 = File {
         id: 0,
         server_id: 14,
         added_date: 1625079601,
         finish_date: None,
+        ..Default::default()
     };
     let file3 = File {
         id: 0,
         server_id: 54,
         added_date: 1625079602,
         finish_date: None,
+        ..Default::default()
     };
 
     let id1 = db.create_or_update_file(file1).await;
     let id2 = db.create_or_update_file(file2).await;
     let id3 = db.create_or_update_file(file3).await;
 
-    db.remove_no_matching_files_ids(&vec![id1, id3]).await;
+    db.remove_no_matching_files_ids("", &[id1, id3]).await;
 
     assert!(
         get_file_by_id(&db, id1).await.is_some(),
@@ -222,12 +232,14 @@ async fn test_list_files() {
         server_id: 1,
         added_date: 1625079600,
         finish_date: None,
+        ..Default::default()
     };
     let file2 = File {
         id: 0,
         server_id: 2,
         added_date: 1625079601,
         finish_date: Some(1625083200),
+        ..Default::default()
     };
 
     db.create_or_update_file(file1.clone()).await;
@@ -245,6 +257,7 @@ async fn test_list_files() {
                 server_id: row.get(1)?,
                 added_date: row.get(2)?,
                 finish_date: row.get(3)?,
+                ..Default::default()
             })
         })
         .expect("Failed to query file table")
@@ -266,19 +279,456 @@ async fn test_get_file_by_server_id() {
         server_id: 42,
         added_date: 1625079600,
         finish_date: None,
+        ..Default::default()
     };
 
     db.create_or_update_file(file.clone()).await;
 
     let fetched_file = db
-        .get_file_by_server_id(file.server_id)
+        .get_file_by_server_id(&file.instance, file.server_id)
         .await
         .expect("File not found by server ID");
     assert_files_equal(&fetched_file, &file);
 
-    let non_existent_file = db.get_file_by_server_id(999).await;
+    let non_existent_file = db.get_file_by_server_id("", 999).await;
     assert!(
         non_existent_file.is_none(),
         "Non-existent file should return None"
     );
 }
+
+#[tokio::test]
+async fn test_files_of_different_instances() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let file1 = File {
+        id: 0,
+        instance: "box1".to_string(),
+        server_id: 1,
+        added_date: 1625079600,
+        finish_date: None,
+        ..Default::default()
+    };
+    let file2 = File {
+        id: 0,
+        instance: "box2".to_string(),
+        server_id: 1,
+        added_date: 1625079700,
+        finish_date: Some(1625083200),
+        ..Default::default()
+    };
+
+    let id1 = db.create_or_update_file(file1.clone()).await;
+    let id2 = db.create_or_update_file(file2.clone()).await;
+    assert_ne!(
+        id1, id2,
+        "Same server ID in different instances are different files"
+    );
+
+    let fetched_file1 = db
+        .get_file_by_server_id("box1", 1)
+        .await
+        .expect("File not found by server ID");
+    assert_files_equal(&fetched_file1, &file1);
+    let fetched_file2 = db
+        .get_file_by_server_id("box2", 1)
+        .await
+        .expect("File not found by server ID");
+    assert_files_equal(&fetched_file2, &file2);
+
+    // removing the files of one instance does not affect the other one
+    db.remove_no_matching_files_ids("box1", &[]).await;
+    assert!(db.list_of_file_ids("box1").await.is_empty());
+    assert_eq!(db.list_of_file_ids("box2").await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_replace_plan() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let entry = |server_id: i32, due_date: i64| PlanEntry {
+        id: 0,
+        instance: "box1".to_string(),
+        server_id,
+        name: format!("torrent-{}", server_id),
+        reason: "lifetime".to_string(),
+        due_date,
+        planned_date: 1625079600,
+        rule: (server_id == 1).then(|| "movies".to_string()),
+        mode: if server_id == 1 {
+            "keep_data".to_string()
+        } else {
+            "delete_data".to_string()
+        },
+    };
+
+    db.replace_plan("box1", &[entry(1, 20), entry(2, 10)]).await;
+    db.replace_plan("box2", &[entry(3, 30)]).await;
+
+    let plan = db.list_plan("box1").await;
+    assert_eq!(plan.len(), 2);
+    assert_eq!(plan[0].server_id, 2, "Plan should be sorted by due date");
+    assert_eq!(plan[1].server_id, 1);
+    assert_eq!(plan[0].name, "torrent-2");
+    assert_eq!(plan[0].rule, None);
+    assert_eq!(plan[1].rule, Some("movies".to_string()));
+    assert_eq!(plan[0].mode, "delete_data");
+    assert_eq!(plan[1].mode, "keep_data");
+
+    // a new plan replaces the previous one of the same instance only
+    db.replace_plan("box1", &[entry(4, 40)]).await;
+    let plan = db.list_plan("box1").await;
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].server_id, 4);
+    assert_eq!(db.list_plan("box2").await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_estimated_finish_date_is_replaced() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let file = File {
+        id: 0,
+        server_id: 1,
+        added_date: 1625079600,
+        finish_date: Some(1625090000),
+        finish_date_estimated: true,
+        ..Default::default()
+    };
+    let id = db.create_or_update_file(file.clone()).await;
+
+    // a later estimated date does not replace the first one
+    db.create_or_update_file(File {
+        finish_date: Some(1625095000),
+        ..file.clone()
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.finish_date, Some(1625090000));
+
+    // an earlier one does, the torrent was already finished by then
+    db.create_or_update_file(File {
+        finish_date: Some(1625085000),
+        ..file.clone()
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.finish_date, Some(1625085000));
+    assert!(stored.finish_date_estimated);
+
+    // the date reported by the client does
+    db.create_or_update_file(File {
+        finish_date: Some(1625080000),
+        finish_date_estimated: false,
+        ..file.clone()
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.id, id);
+    assert_eq!(stored.finish_date, Some(1625080000));
+    assert!(!stored.finish_date_estimated);
+
+    // and is kept afterwards
+    db.create_or_update_file(File {
+        finish_date: Some(1625099000),
+        finish_date_estimated: false,
+        ..file
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.finish_date, Some(1625080000));
+}
+
+#[tokio::test]
+async fn test_seeding_times_migration_backfill() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    db.create_or_update_file(File {
+        server_id: 1,
+        added_date: 1625079600,
+        finish_date: Some(1625090000),
+        ..Default::default()
+    })
+    .await;
+    db.create_or_update_file(File {
+        server_id: 2,
+        added_date: 1625079600,
+        ..Default::default()
+    })
+    .await;
+
+    // go back to the schema before the migration
+    db.connection
+        .lock()
+        .await
+        .execute_batch(
+            "ALTER TABLE file DROP COLUMN secondsSeeding;
+            ALTER TABLE file DROP COLUMN activityDate;
+            ALTER TABLE file DROP COLUMN finishDateEstimated;
+            DELETE FROM migration_version WHERE version = 6;",
+        )
+        .expect("Failed to revert migration");
+    db.apply_migrations().await;
+
+    let finished = db.get_file_by_server_id("", 1).await.unwrap();
+    assert!(finished.finish_date_estimated);
+    let downloading = db.get_file_by_server_id("", 2).await.unwrap();
+    assert!(!downloading.finish_date_estimated);
+}
+
+#[tokio::test]
+async fn test_trash_entries() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let entry = |instance: &str, name: &str, trashed_date: i64| TrashEntry {
+        id: 0,
+        instance: instance.to_string(),
+        name: name.to_string(),
+        path: format!("/trash/{}", name),
+        size: 100,
+        trashed_date,
+    };
+    db.add_trash_entry(&entry("box1", "b", 20)).await;
+    db.add_trash_entry(&entry("box1", "a", 10)).await;
+    db.add_trash_entry(&entry("box2", "c", 10)).await;
+
+    let trash = db.list_trash("box1").await;
+    assert_eq!(trash.len(), 2);
+    assert_eq!(trash[0].name, "a", "Trash should be sorted by trashed date");
+    assert_eq!(trash[0].path, "/trash/a");
+    assert_eq!(trash[1].name, "b");
+
+    db.remove_trash_entry(trash[0].id).await;
+    let trash = db.list_trash("box1").await;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].name, "b");
+    assert_eq!(db.list_trash("box2").await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_deletion_history() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let entry = |name: &str, deleted_date: i64, result: &str| HistoryEntry {
+        id: 0,
+        instance: "box1".to_string(),
+        hash: "a".repeat(40),
+        name: name.to_string(),
+        size: 100,
+        reason: "lifetime".to_string(),
+        rule: Some("movies".to_string()),
+        mode: "delete_data".to_string(),
+        deleted_date,
+        result: result.to_string(),
+    };
+    db.add_history_entry(&entry("Ubuntu 24.04", 30, "success"))
+        .await;
+    db.add_history_entry(&entry("debian.iso", 10, "success"))
+        .await;
+    db.add_history_entry(&entry("ubuntu 25.10", 20, "Connection refused"))
+        .await;
+
+    let history = db.list_history(&HistoryFilter::default()).await;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].name, "debian.iso", "History sorted by date");
+    assert_eq!(history[0].rule, Some("movies".to_string()));
+    assert_eq!(history[1].result, "Connection refused");
+
+    let filter = HistoryFilter {
+        from: Some(20),
+        to: Some(30),
+        name: None,
+    };
+    let history = db.list_history(&filter).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "ubuntu 25.10");
+
+    let filter = HistoryFilter {
+        name: Some("UBUNTU".to_string()),
+        ..Default::default()
+    };
+    let history = db.list_history(&filter).await;
+    assert_eq!(history.len(), 2, "Name filter is case insensitive");
+}
+
+#[tokio::test]
+async fn test_last_digest_date() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    assert_eq!(db.last_digest_date().await, None);
+    db.set_last_digest_date(100).await;
+    db.set_last_digest_date(200).await;
+    assert_eq!(db.last_digest_date().await, Some(200));
+}
+
+#[tokio::test]
+async fn test_protections() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let protection = |kind: &str, value: &str| Protection {
+        id: 0,
+        kind: kind.to_string(),
+        value: value.to_string(),
+        created_date: 10,
+    };
+    let first = db.add_protection(&protection("label", "release")).await;
+    let second = db.add_protection(&protection("name", "^Own")).await;
+    assert_ne!(first, second);
+
+    let protections = db.list_protections().await;
+    assert_eq!(protections.len(), 2);
+    assert_eq!(protections[0].id, first);
+    assert_eq!(protections[0].kind, "label");
+    assert_eq!(protections[0].value, "release");
+    assert_eq!(protections[0].created_date, 10);
+    assert_eq!(protections[1].kind, "name");
+
+    assert!(db.remove_protection(first).await);
+    assert!(
+        !db.remove_protection(first).await,
+        "Protection was already removed"
+    );
+    let protections = db.list_protections().await;
+    assert_eq!(protections.len(), 1);
+    assert_eq!(protections[0].id, second);
+}
+
+#[tokio::test]
+async fn test_set_file_stopped() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let id = db
+        .create_or_update_file(File {
+            server_id: 1,
+            added_date: 1625079600,
+            finish_date: Some(1625080000),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(
+        db.get_file_by_server_id("", 1).await.unwrap().stopped_date,
+        None
+    );
+
+    db.set_file_stopped(id, 1625090000).await;
+    // updating the file from the client keeps the stopped date
+    db.create_or_update_file(File {
+        server_id: 1,
+        added_date: 1625079600,
+        finish_date: Some(1625080000),
+        ..Default::default()
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.stopped_date, Some(1625090000));
+}
+
+#[tokio::test]
+async fn test_files_are_tracked_by_hash() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let file = |server_id: i32, hash: &str, added_date: i64| File {
+        server_id,
+        hash: hash.to_string(),
+        added_date,
+        ..Default::default()
+    };
+    let first = db.create_or_update_file(file(1, "aaa", 100)).await;
+    let second = db.create_or_update_file(file(2, "bbb", 200)).await;
+
+    // the client restarted and swapped the ids of the torrents
+    assert_eq!(db.create_or_update_file(file(2, "aaa", 100)).await, first);
+    assert_eq!(db.create_or_update_file(file(1, "bbb", 200)).await, second);
+    let stored = db.get_file_by_hash("", "aaa").await.unwrap();
+    assert_eq!(stored.id, first);
+    assert_eq!(stored.server_id, 2);
+    assert_eq!(stored.added_date, 100);
+
+    // a new torrent reusing the id of a tracked one is a new file
+    let third = db.create_or_update_file(file(1, "ccc", 300)).await;
+    assert_ne!(third, second);
+    assert_eq!(
+        db.get_file_by_hash("", "ccc").await.unwrap().added_date,
+        300
+    );
+}
+
+#[tokio::test]
+async fn test_files_without_hash_are_reconciled() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    // file tracked before the hashes were stored
+    let id = db
+        .create_or_update_file(File {
+            server_id: 1,
+            added_date: 100,
+            ..Default::default()
+        })
+        .await;
+    assert!(
+        db.get_file_by_server_id("", 1)
+            .await
+            .unwrap()
+            .hash
+            .is_empty()
+    );
+
+    let reconciled = db
+        .create_or_update_file(File {
+            server_id: 1,
+            hash: "aaa".to_string(),
+            added_date: 100,
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(reconciled, id);
+    assert_eq!(db.get_file_by_hash("", "aaa").await.unwrap().id, id);
+}
+
+#[tokio::test]
+async fn test_file_metadata_is_stored() {
+    let mut db = Database::new(None);
+    db.connect().await.expect("Failed to connect to database");
+
+    let file = File {
+        server_id: 1,
+        hash: "c12fe1c06bba254a9dc9f519b335aa7c1367a88a".to_string(),
+        added_date: 1625079600,
+        name: "debian.iso".to_string(),
+        size: 4000,
+        download_dir: "/downloads".to_string(),
+        trackers: vec!["tracker.org".to_string()],
+        labels: vec!["linux".to_string(), "iso".to_string()],
+        ..Default::default()
+    };
+    db.create_or_update_file(file.clone()).await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.name, "debian.iso");
+    assert_eq!(stored.size, 4000);
+    assert_eq!(stored.download_dir, "/downloads");
+    assert_eq!(stored.trackers, vec!["tracker.org"]);
+    assert_eq!(stored.labels, vec!["linux", "iso"]);
+    assert_eq!(stored.to_string(), "debian.iso (c12fe1c0)");
+
+    // the metadata is refreshed on every scan
+    db.create_or_update_file(File {
+        name: "debian-12.iso".to_string(),
+        labels: vec![],
+        ..file
+    })
+    .await;
+    let stored = db.get_file_by_server_id("", 1).await.unwrap();
+    assert_eq!(stored.name, "debian-12.iso");
+    assert!(stored.labels.is_empty());
+}
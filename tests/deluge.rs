@@ -0,0 +1,114 @@
+use fp::logic::api::TorrentClient;
+use fp::logic::api::deluge::DelugeApi;
+use mockito::{Matcher, ServerGuard};
+
+const SESSION: &str = "_session_id=5a3c0e4bd5c3b0a2f6d1";
+
+fn setup_login_mocks(server: &mut ServerGuard, password: &str) {
+    server
+        .mock("POST", "/json")
+        .match_body(Matcher::PartialJsonString(format!(
+            "{{\"method\": \"auth.login\", \"params\": [\"{}\"]}}",
+            password
+        )))
+        .with_status(200)
+        .with_header("set-cookie", format!("{}; Path=/json", SESSION).as_str())
+        .with_body("{\"result\": true, \"error\": null, \"id\": 1}")
+        .create();
+    server
+        .mock("POST", "/json")
+        .match_header("cookie", SESSION)
+        .match_body(Matcher::PartialJsonString(
+            "{\"method\": \"web.connected\"}".to_string(),
+        ))
+        .with_status(200)
+        .with_body("{\"result\": true, \"error\": null, \"id\": 2}")
+        .create();
+}
+
+#[tokio::test]
+async fn test_deluge_list_files() {
+    let mut server = mockito::Server::new_async().await;
+    let password = "deluge";
+
+    setup_login_mocks(&mut server, password);
+    server
+        .mock("POST", "/json")
+        .match_header("cookie", SESSION)
+        .match_body(Matcher::PartialJsonString(
+            "{\"method\": \"core.get_torrents_status\"}".to_string(),
+        ))
+        .with_status(200)
+        .with_body("{\"result\": {\"aaa\": {\"time_added\": 1763580763.0, \"completed_time\": 0, \"progress\": 50.0, \"is_finished\": false}}, \"error\": null, \"id\": 3}")
+        .create();
+
+    let mut api = DelugeApi::new(password.to_string(), &server.url()).unwrap();
+
+    match api.fetch_files().await {
+        Ok(files) => {
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].id, 0);
+            assert_eq!(files[0].server_id, 1);
+            assert_eq!(files[0].added_date, 1763580763);
+            assert_eq!(files[0].finish_date, None);
+        }
+        Err(e) => panic!("API fetch_files failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_deluge_list_files_with_finish() {
+    let mut server = mockito::Server::new_async().await;
+    let password = "deluge";
+
+    setup_login_mocks(&mut server, password);
+    server
+        .mock("POST", "/json")
+        .match_header("cookie", SESSION)
+        .match_body(Matcher::PartialJsonString(
+            "{\"method\": \"core.get_torrents_status\"}".to_string(),
+        ))
+        .with_status(200)
+        .with_body("{\"result\": {\"aaa\": {\"time_added\": 1763580763.0, \"completed_time\": 1763580800.0, \"progress\": 100.0, \"is_finished\": true}}, \"error\": null, \"id\": 3}")
+        .create();
+
+    let mut api = DelugeApi::new(password.to_string(), &server.url()).unwrap();
+
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    assert_eq!(files[0].finish_date, Some(1763580800));
+}
+
+#[tokio::test]
+async fn test_deluge_delete_file() {
+    let mut server = mockito::Server::new_async().await;
+    let password = "deluge";
+
+    setup_login_mocks(&mut server, password);
+    server
+        .mock("POST", "/json")
+        .match_header("cookie", SESSION)
+        .match_body(Matcher::PartialJsonString(
+            "{\"method\": \"core.get_torrents_status\"}".to_string(),
+        ))
+        .with_status(200)
+        .with_body("{\"result\": {\"aaa\": {\"time_added\": 1763580763.0, \"completed_time\": 1763580800.0, \"progress\": 100.0, \"is_finished\": true}}, \"error\": null, \"id\": 3}")
+        .create();
+    let delete_mock = server
+        .mock("POST", "/json")
+        .match_header("cookie", SESSION)
+        .match_body(Matcher::PartialJsonString(
+            "{\"method\": \"core.remove_torrents\", \"params\": [[\"aaa\"], true]}".to_string(),
+        ))
+        .with_status(200)
+        .with_body("{\"result\": [], \"error\": null, \"id\": 4}")
+        .create();
+
+    let mut api = DelugeApi::new(password.to_string(), &server.url()).unwrap();
+
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    match api.delete_file(&[files[0].hash.clone()], true).await {
+        Ok(_) => {}
+        Err(e) => panic!("API delete_file failed: {}", e),
+    }
+    delete_mock.assert();
+}
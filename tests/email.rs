@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use fp::logic::api::{Capabilities, TorrentClient};
+use fp::logic::database::models::File;
+use fp::logic::email::{DigestPeriod, EmailSettings, Mailer, SmtpTls};
+use fp::{DEFAULT_INSTANCE, Monitor};
+
+const TEST_TIMEOUT_SECS: u64 = 4;
+
+/// Minimal SMTP server keeping the messages it receives.
+async fn smtp_sink() -> (u16, Arc<std::sync::Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let messages = Arc::new(std::sync::Mutex::new(vec![]));
+    let received = messages.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = line.to_uppercase();
+                if command.starts_with("DATA") {
+                    writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                    let mut message = String::new();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line == "." {
+                            break;
+                        }
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    received.lock().unwrap().push(message);
+                    writer.write_all(b"250 queued\r\n").await.unwrap();
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    writer.write_all(b"250 ok\r\n").await.unwrap();
+                }
+            }
+        }
+    });
+    (port, messages)
+}
+
+fn settings(port: u16) -> EmailSettings {
+    EmailSettings {
+        host: "127.0.0.1".to_string(),
+        port: Some(port),
+        tls: SmtpTls::None,
+        from: "file_purge <purge@example.org>".to_string(),
+        to: vec!["alice@example.org".to_string()],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_mailer_sends_to_sink() {
+    let (port, messages) = smtp_sink().await;
+    let mailer = Mailer::new(settings(port)).unwrap();
+
+    mailer
+        .send("file_purge digest", "Space freed: 1.0 GiB\n".to_string())
+        .await
+        .expect("Failed to send the email");
+
+    let messages = messages.lock().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("Subject: file_purge digest"));
+    assert!(messages[0].contains("To: alice@example.org"));
+    assert!(messages[0].contains("Space freed: 1.0 GiB"));
+}
+
+#[tokio::test]
+async fn test_mailer_rejects_invalid_addresses() {
+    let mut invalid = settings(25);
+    invalid.to = vec!["not an address".to_string()];
+    assert!(Mailer::new(invalid).is_err());
+
+    let mut no_recipient = settings(25);
+    no_recipient.to = vec![];
+    assert!(Mailer::new(no_recipient).is_err());
+}
+
+struct UnreachableClient {}
+
+#[async_trait::async_trait]
+impl TorrentClient for UnreachableClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        Err("Connection refused".to_string())
+    }
+
+    async fn delete_file(&mut self, _hashes: &[String], _delete_data: bool) -> Result<(), String> {
+        Err("Connection refused".to_string())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_sends_alerts() {
+    let (port, messages) = smtp_sink().await;
+    let mailer = Mailer::new(EmailSettings {
+        alerts: true,
+        digest: Some(DigestPeriod::Daily),
+        ..settings(port)
+    })
+    .unwrap();
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor =
+        Monitor::with_client(Box::new(UnreachableClient {}), None, Some(0), None, None);
+    monitor.set_mailer(Some(mailer));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while messages.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for the alert");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // the next failed scans are not alerted again
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    let messages = messages.lock().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains(&format!(
+        "Subject: file_purge: scan_failed on {}",
+        DEFAULT_INSTANCE
+    )));
+    assert!(messages[0].contains("Scan failed: Connection refused"));
+}
@@ -7,7 +7,8 @@ use mockito::{Matcher, Mock, ServerGuard};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
-use fp::Monitor;
+use fp::logic::api::transmission::TransmissionApi;
+use fp::{DEFAULT_INSTANCE, Instance, Monitor};
 
 const TEST_TIMEOUT_SECS: i64 = 4;
 
@@ -21,7 +22,7 @@ struct ReqListArgs {
 struct ReqDeleteArgs {
     #[serde(rename = "delete-local-data")]
     pub delete_local_data: bool,
-    pub ids: Vec<i64>,
+    pub ids: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -40,12 +41,14 @@ struct ReqListBody {
 #[derive(Deserialize, Serialize, Debug)]
 struct ResItem {
     id: i64,
+    #[serde(rename = "hashString")]
+    hash_string: String,
     #[serde(rename = "addedDate")]
     added_date: i64,
     #[serde(rename = "isFinished")]
     is_finished: bool,
     #[serde(rename = "percentDone")]
-    percent_done: f64
+    percent_done: f64,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -103,6 +106,15 @@ fn setup_base_mock(
 
 #[tokio::test]
 async fn test_end_to_end() {
+    run_end_to_end(true).await;
+}
+
+#[tokio::test]
+async fn test_end_to_end_keep_data() {
+    run_end_to_end(false).await;
+}
+
+async fn run_end_to_end(delete_data: bool) {
     let test_start_time = get_now_timestamp();
 
     let mut server = mockito::Server::new_async().await;
@@ -115,12 +127,14 @@ async fn test_end_to_end() {
             torrents: vec![
                 ResItem {
                     id: 1,
+                    hash_string: "a".repeat(40),
                     added_date: get_now_timestamp(),
                     is_finished: false,
                     percent_done: 0.5,
                 },
                 ResItem {
                     id: 2,
+                    hash_string: "b".repeat(40),
                     added_date: get_now_timestamp(),
                     is_finished: true,
                     percent_done: 1.0,
@@ -142,9 +156,9 @@ async fn test_end_to_end() {
         "\"torrent-get\"".to_string(),
         409,
     )
-    .with_body_from_request(move |request| {
+    .match_header("x-transmission-session-id", Matcher::Missing)
+    .with_body_from_request(move |_request| {
         println!("Exchanging session id");
-        assert!(!request.has_header("x-transmission-session-id"));
         session_exchanged_clone.store(true, Ordering::SeqCst);
         "start session id exchange".into()
     })
@@ -178,11 +192,14 @@ async fn test_end_to_end() {
             serde_json::from_str(&body).expect("Failed to parse delete request body to json");
 
         if let ReqArgs::Delete(args) = req_body.arguments {
-            assert!(args.delete_local_data);
-            for remove_id in args.ids {
+            assert_eq!(args.delete_local_data, delete_data);
+            // torrents are removed by hash
+            for remove_hash in args.ids {
                 let mut res = list_res_clone.lock().unwrap();
                 if let ResArgs::List(res_args) = &mut res.arguments {
-                    res_args.torrents.retain(|item| item.id != remove_id);
+                    res_args
+                        .torrents
+                        .retain(|item| item.hash_string != remove_hash);
                 }
             }
         }
@@ -195,15 +212,30 @@ async fn test_end_to_end() {
     // run monitor in thread
     let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
     let stop_signal_clone = stop_signal.clone();
-    let mut monitor = Monitor::new(
-        format!("{}/transmission/rpc", server.url()).as_str(),
-        None,
-        Some(0),
-        Some(0),
-        Some(0),
-        username,
-        password,
-    );
+    let api_url = format!("{}/transmission/rpc", server.url());
+    let mut monitor = if delete_data {
+        Monitor::new(
+            api_url.as_str(),
+            None,
+            Some(0),
+            Some(0),
+            Some(0),
+            username,
+            password,
+        )
+    } else {
+        let mut instance = Instance::new(
+            DEFAULT_INSTANCE,
+            Box::new(
+                TransmissionApi::new(username.to_string(), password.to_string(), api_url.as_str())
+                    .unwrap(),
+            ),
+            Some(0),
+            Some(0),
+        );
+        instance.set_delete_data(false);
+        Monitor::with_instances(vec![instance], None, Some(0))
+    };
     let rt = tokio::runtime::Runtime::new().unwrap();
     let app_thread = rt.spawn(async move {
         monitor.run(Some(stop_signal_clone)).await;
@@ -237,7 +269,6 @@ async fn test_end_to_end() {
             session_id_clone.as_str()
         );
 
-
         let res = list_res_clone.lock().unwrap();
         println!("{}", serde_json::to_string(&*res).unwrap().as_str());
         serde_json::to_string(&*res).unwrap().into()
@@ -0,0 +1,1202 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use fp::logic::api::{Capabilities, TorrentClient};
+use fp::logic::database::Database;
+use fp::logic::database::models::{File, SEED_RATIO_MODE_SINGLE};
+use fp::logic::history::HistoryFilter;
+use fp::logic::notify::{Webhook, WebhookPreset};
+use fp::logic::plan::{FreeSpaceOrder, FreeSpacePolicy};
+use fp::logic::protection::new_protection;
+use fp::logic::rules::parse_rules;
+use fp::logic::trash::{TrashPolicy, purge};
+use fp::{DEFAULT_INSTANCE, Instance, InstanceSettings, Monitor, Reload};
+
+const TEST_TIMEOUT_SECS: u64 = 4;
+
+struct FakeClient {
+    files: Vec<File>,
+    deleted: Arc<std::sync::Mutex<Vec<i32>>>,
+}
+
+/// Hash of a fake torrent, derived from its server id when not set.
+fn hash_of(file: &File) -> String {
+    if file.hash.is_empty() {
+        format!("{:040x}", file.server_id)
+    } else {
+        file.hash.clone()
+    }
+}
+
+impl FakeClient {
+    fn server_ids(&self, hashes: &[String]) -> Vec<i32> {
+        self.files
+            .iter()
+            .filter(|file| hashes.contains(&hash_of(file)))
+            .map(|file| file.server_id)
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for FakeClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        let deleted = self.deleted.lock().unwrap();
+        Ok(self
+            .files
+            .iter()
+            .filter(|file| !deleted.contains(&file.server_id))
+            .map(|file| File {
+                hash: hash_of(file),
+                ..file.clone()
+            })
+            .collect())
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], _delete_data: bool) -> Result<(), String> {
+        let ids = self.server_ids(hashes);
+        self.deleted.lock().unwrap().extend(ids);
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// Client whose download directories are running out of space.
+struct FullDiskClient {
+    client: FakeClient,
+    free_space: i64,
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for FullDiskClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        self.client.fetch_files().await
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        // deleting files releases their space
+        let released: i64 = self
+            .client
+            .files
+            .iter()
+            .filter(|file| hashes.contains(&hash_of(file)))
+            .map(|file| file.size)
+            .sum();
+        self.free_space += released;
+        self.client.delete_file(hashes, delete_data).await
+    }
+
+    async fn free_space(&mut self, _path: &str) -> Result<i64, String> {
+        Ok(self.free_space)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            free_space: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Client that moves the data of its torrents on the local disk.
+struct MovingClient {
+    client: FakeClient,
+    removed_with_data: Arc<std::sync::Mutex<Vec<bool>>>,
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for MovingClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        self.client.fetch_files().await
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        self.removed_with_data.lock().unwrap().push(delete_data);
+        self.client.delete_file(hashes, delete_data).await
+    }
+
+    async fn move_file(&mut self, hashes: &[String], location: &str) -> Result<(), String> {
+        for file in self
+            .client
+            .files
+            .iter()
+            .filter(|file| hashes.contains(&hash_of(file)))
+        {
+            let directory = std::path::Path::new(location);
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+            std::fs::write(directory.join(&file.name), "data").map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            keep_data: true,
+            move_data: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Client that can stop its torrents.
+struct StoppingClient {
+    client: FakeClient,
+    stopped: Arc<std::sync::Mutex<Vec<i32>>>,
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for StoppingClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        self.client.fetch_files().await
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        self.client.delete_file(hashes, delete_data).await
+    }
+
+    async fn stop_file(&mut self, hashes: &[String]) -> Result<(), String> {
+        let ids = self.client.server_ids(hashes);
+        self.stopped.lock().unwrap().extend(ids);
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            stop: true,
+            ..Default::default()
+        }
+    }
+}
+
+fn get_now_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[tokio::test]
+async fn test_monitor_with_custom_client() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = FakeClient {
+        files: vec![
+            // recently added and still downloading
+            File {
+                id: 0,
+                server_id: 1,
+                added_date: now,
+                finish_date: None,
+                ..Default::default()
+            },
+            // finished long ago
+            File {
+                id: 0,
+                server_id: 2,
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                ..Default::default()
+            },
+            // downloading for too long
+            File {
+                id: 0,
+                server_id: 3,
+                added_date: now - 5000,
+                finish_date: None,
+                ..Default::default()
+            },
+        ],
+        deleted: deleted.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(Box::new(client), None, Some(0), Some(3600), Some(100));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().len() < 2 {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    let mut deleted = deleted.lock().unwrap().clone();
+    deleted.sort();
+    assert_eq!(deleted, vec![2, 3]);
+}
+
+#[tokio::test]
+async fn test_monitor_with_multiple_instances() {
+    let now = get_now_timestamp();
+    let deleted1 = Arc::new(std::sync::Mutex::new(vec![]));
+    let deleted2 = Arc::new(std::sync::Mutex::new(vec![]));
+    // both instances use the same server ids
+    let files = vec![
+        File {
+            id: 0,
+            server_id: 1,
+            added_date: now - 1000,
+            finish_date: Some(now - 500),
+            ..Default::default()
+        },
+        File {
+            id: 0,
+            server_id: 2,
+            added_date: now,
+            finish_date: None,
+            ..Default::default()
+        },
+    ];
+    let client1 = FakeClient {
+        files: files.clone(),
+        deleted: deleted1.clone(),
+    };
+    let client2 = FakeClient {
+        files,
+        deleted: deleted2.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_instances(
+        vec![
+            // keeps finished files
+            Instance::new("box1", Box::new(client1), Some(3600), Some(3600)),
+            Instance::new("box2", Box::new(client2), Some(3600), Some(100)),
+        ],
+        None,
+        Some(0),
+    );
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted2.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    assert!(deleted1.lock().unwrap().is_empty());
+    assert_eq!(deleted2.lock().unwrap().clone(), vec![1]);
+}
+
+#[tokio::test]
+async fn test_monitor_dry_run() {
+    let now = get_now_timestamp();
+    let database_path = std::env::temp_dir().join(format!("fp_dry_run_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = FakeClient {
+        files: vec![
+            File {
+                id: 0,
+                server_id: 1,
+                added_date: now,
+                finish_date: None,
+                ..Default::default()
+            },
+            File {
+                id: 0,
+                server_id: 2,
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                ..Default::default()
+            },
+        ],
+        deleted: deleted.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(
+        Box::new(client),
+        Some(database_path.to_str().unwrap().to_string()),
+        Some(0),
+        Some(3600),
+        Some(100),
+    );
+    monitor.set_dry_run(true);
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let mut db = Database::new(Some(database_path.to_str().unwrap().to_string()));
+    let start = std::time::Instant::now();
+    let plan = loop {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for the plan");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if !database_path.exists() {
+            continue;
+        }
+        db.connect().await.expect("Failed to connect to database");
+        let plan = db.list_plan(DEFAULT_INSTANCE).await;
+        if plan.len() == 2 {
+            break plan;
+        }
+    };
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+    let _ = std::fs::remove_file(&database_path);
+
+    assert!(
+        deleted.lock().unwrap().is_empty(),
+        "Dry run must not delete"
+    );
+    assert_eq!(plan[0].server_id, 2);
+    assert_eq!(plan[0].reason, "lifetime_after_copied");
+    assert_eq!(plan[0].due_date, now - 400);
+    assert_eq!(plan[1].server_id, 1);
+    assert_eq!(plan[1].reason, "lifetime");
+    assert_eq!(plan[1].due_date, now + 3600);
+}
+
+#[tokio::test]
+async fn test_monitor_with_rules() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let finished = |server_id: i32, name: &str, labels: Vec<String>| File {
+        id: 0,
+        server_id,
+        added_date: now - 1000,
+        finish_date: Some(now - 500),
+        name: name.to_string(),
+        labels,
+        ..Default::default()
+    };
+    let client = FakeClient {
+        files: vec![
+            // kept by the first rule
+            finished(1, "release.mkv", vec!["release".to_string()]),
+            // removed early by the second rule
+            File {
+                finish_date: None,
+                ..finished(2, "debian.iso", vec![])
+            },
+            // uses the instance lifetimes
+            finished(3, "movie.mkv", vec![]),
+        ],
+        deleted: deleted.clone(),
+    };
+
+    let rules = parse_rules(
+        r#"
+        [[rule]]
+        name = "releases"
+        action = "keep"
+        [rule.match]
+        label = "release"
+
+        [[rule]]
+        name = "isos"
+        lifetime = 100
+        [rule.match]
+        name = "\\.iso$"
+        "#,
+    )
+    .expect("Failed to parse rules");
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(Box::new(client), None, Some(0), Some(3600), Some(100));
+    monitor.set_rules(rules);
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().len() < 2 {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    let mut deleted = deleted.lock().unwrap().clone();
+    deleted.sort();
+    assert_eq!(deleted, vec![2, 3]);
+}
+
+#[tokio::test]
+async fn test_monitor_with_ratio() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let seeding = |server_id: i32, upload_ratio: f64| File {
+        id: 0,
+        server_id,
+        added_date: now - 1000,
+        finish_date: Some(now - 500),
+        upload_ratio,
+        ..Default::default()
+    };
+    let client = FakeClient {
+        files: vec![
+            // below the ratio target
+            seeding(1, 0.5),
+            // reached the ratio target
+            seeding(2, 2.0),
+            // reached the ratio limit set in the client
+            File {
+                seed_ratio_limit: 1.0,
+                seed_ratio_mode: SEED_RATIO_MODE_SINGLE,
+                ..seeding(3, 1.2)
+            },
+            // reached the ratio but still downloading
+            File {
+                finish_date: None,
+                ..seeding(4, 3.0)
+            },
+        ],
+        deleted: deleted.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut instance = Instance::new(DEFAULT_INSTANCE, Box::new(client), Some(3600), Some(3600));
+    instance.set_ratio(Some(2.0), true);
+    let mut monitor = Monitor::with_instances(vec![instance], None, Some(0));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().len() < 2 {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    let mut deleted = deleted.lock().unwrap().clone();
+    deleted.sort();
+    assert_eq!(deleted, vec![2, 3]);
+}
+
+#[tokio::test]
+async fn test_monitor_with_free_space_pressure() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let finished = |server_id: i32, finish_date: i64, size: i64| File {
+        id: 0,
+        server_id,
+        added_date: now - 1000,
+        finish_date: Some(finish_date),
+        size,
+        download_dir: "/downloads".to_string(),
+        ..Default::default()
+    };
+    let client = FullDiskClient {
+        client: FakeClient {
+            files: vec![
+                finished(1, now - 300, 100),
+                finished(2, now - 200, 500),
+                finished(3, now - 100, 300),
+                // still downloading
+                File {
+                    finish_date: None,
+                    ..finished(4, now, 1000)
+                },
+            ],
+            deleted: deleted.clone(),
+        },
+        free_space: 100,
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut instance = Instance::new(DEFAULT_INSTANCE, Box::new(client), Some(3600), Some(3600));
+    instance.set_free_space(Some(FreeSpacePolicy {
+        min_free_space: 200,
+        target_free_space: 700,
+        order: FreeSpaceOrder::Largest,
+    }));
+    let mut monitor = Monitor::with_instances(vec![instance], None, Some(0));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().len() < 2 {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // give the monitor the time to run more scans
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    // the largest finished files are removed until the target is reached
+    let mut deleted = deleted.lock().unwrap().clone();
+    deleted.sort();
+    assert_eq!(deleted, vec![2, 3]);
+}
+
+#[tokio::test]
+async fn test_monitor_with_trash() {
+    let now = get_now_timestamp();
+    let database_path = std::env::temp_dir().join(format!("fp_trash_{}.db", std::process::id()));
+    let trash_dir = std::env::temp_dir().join(format!("fp_trash_{}", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+    let _ = std::fs::remove_dir_all(&trash_dir);
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let removed_with_data = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = MovingClient {
+        client: FakeClient {
+            files: vec![File {
+                id: 0,
+                server_id: 1,
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                name: "movie.mkv".to_string(),
+                size: 4,
+                ..Default::default()
+            }],
+            deleted: deleted.clone(),
+        },
+        removed_with_data: removed_with_data.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut instance = Instance::new(DEFAULT_INSTANCE, Box::new(client), Some(3600), Some(100));
+    instance.set_trash(Some(TrashPolicy {
+        directory: trash_dir.to_str().unwrap().to_string(),
+        grace_period: 3600,
+        max_size: None,
+    }));
+    let mut monitor = Monitor::with_instances(
+        vec![instance],
+        Some(database_path.to_str().unwrap().to_string()),
+        Some(0),
+    );
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    let mut db = Database::new(Some(database_path.to_str().unwrap().to_string()));
+    db.connect().await.expect("Failed to connect to database");
+    let trash = db.list_trash(DEFAULT_INSTANCE).await;
+    let _ = std::fs::remove_file(&database_path);
+
+    // the torrent is removed from the client but its data is kept in the trash
+    assert_eq!(removed_with_data.lock().unwrap().clone(), vec![false]);
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].name, "movie.mkv");
+    assert_eq!(trash[0].size, 4);
+    assert!(trash[0].path.starts_with(trash_dir.to_str().unwrap()));
+    assert!(
+        std::path::Path::new(&trash[0].path)
+            .join("movie.mkv")
+            .exists()
+    );
+
+    // the data is purged once the grace period is over
+    let policy = TrashPolicy {
+        directory: trash_dir.to_str().unwrap().to_string(),
+        grace_period: 0,
+        max_size: None,
+    };
+    for entry in policy.entries_to_purge(&trash, now) {
+        purge(entry).expect("Failed to purge the trash");
+    }
+    assert!(!std::path::Path::new(&trash[0].path).exists());
+    let _ = std::fs::remove_dir_all(&trash_dir);
+}
+
+#[tokio::test]
+async fn test_monitor_with_protections() {
+    let now = get_now_timestamp();
+    let database_path =
+        std::env::temp_dir().join(format!("fp_protected_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let finished = |server_id: i32, hash: &str, name: &str, labels: Vec<String>| File {
+        id: 0,
+        server_id,
+        added_date: now - 1000,
+        finish_date: Some(now - 500),
+        hash: hash.to_string(),
+        name: name.to_string(),
+        labels,
+        ..Default::default()
+    };
+    let client = FakeClient {
+        files: vec![
+            finished(1, &"a".repeat(40), "movie.mkv", vec![]),
+            finished(2, &"b".repeat(40), "Own Release.mkv", vec![]),
+            finished(
+                3,
+                &"c".repeat(40),
+                "show.mkv",
+                vec!["seed-forever".to_string()],
+            ),
+            finished(4, &"d".repeat(40), "debian.iso", vec![]),
+        ],
+        deleted: deleted.clone(),
+    };
+
+    // the protections are managed through the database while the service runs
+    let mut db = Database::new(Some(database_path.to_str().unwrap().to_string()));
+    db.connect().await.expect("Failed to connect to database");
+    for (kind, value) in [
+        ("hash", "A".repeat(40)),
+        ("name", "^Own Release".to_string()),
+        ("label", "seed-forever".to_string()),
+    ] {
+        db.add_protection(&new_protection(kind, &value, now).unwrap())
+            .await;
+    }
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(
+        Box::new(client),
+        Some(database_path.to_str().unwrap().to_string()),
+        Some(0),
+        Some(3600),
+        Some(100),
+    );
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // give the service time to remove the protected files if it were to
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+    let history = db.list_history(&HistoryFilter::default()).await;
+    let _ = std::fs::remove_file(&database_path);
+
+    assert_eq!(deleted.lock().unwrap().clone(), vec![4]);
+    // only the removed torrent is recorded in the deletion history
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "debian.iso");
+    assert_eq!(history[0].hash, "d".repeat(40));
+    assert_eq!(history[0].reason, "lifetime_after_copied");
+    assert_eq!(history[0].mode, "delete_data");
+    assert_eq!(history[0].result, "success");
+}
+
+#[tokio::test]
+async fn test_monitor_with_stop() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let stopped = Arc::new(std::sync::Mutex::new(vec![]));
+    let finished = |server_id: i32, finish_date: i64| File {
+        id: 0,
+        server_id,
+        added_date: finish_date - 100,
+        finish_date: Some(finish_date),
+        ..Default::default()
+    };
+    let client = StoppingClient {
+        client: FakeClient {
+            files: vec![
+                // past its lifetime after copied, stopped
+                finished(1, now - 500),
+                // past its stop lifetime, removed
+                finished(2, now - 5000),
+                // still seeding
+                finished(3, now),
+            ],
+            deleted: deleted.clone(),
+        },
+        stopped: stopped.clone(),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut instance = Instance::new(DEFAULT_INSTANCE, Box::new(client), Some(100000), Some(100));
+    instance.set_stop(Some(3600));
+    let mut monitor = Monitor::with_instances(vec![instance], None, Some(0));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // let a few more scans run, the torrent must not be stopped twice
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    assert_eq!(stopped.lock().unwrap().clone(), vec![1]);
+    assert_eq!(deleted.lock().unwrap().clone(), vec![2]);
+}
+
+#[tokio::test]
+async fn test_monitor_status_api() {
+    let now = get_now_timestamp();
+    let client = FakeClient {
+        files: vec![
+            File {
+                server_id: 1,
+                name: "debian.iso".to_string(),
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                ..Default::default()
+            },
+            File {
+                server_id: 2,
+                name: "ubuntu.iso".to_string(),
+                added_date: now,
+                ..Default::default()
+            },
+        ],
+        deleted: Arc::new(std::sync::Mutex::new(vec![])),
+    };
+
+    // reserve a free port for the status API
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(Box::new(client), None, Some(0), Some(3600), Some(100));
+    monitor.set_dry_run(true);
+    monitor.set_status_address(Some(address.clone()));
+    monitor
+        .status()
+        .lock()
+        .await
+        .set_config(serde_json::json!({ "password": "<redacted>" }));
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let get = |path: &str| reqwest::get(format!("http://{}{}", address, path));
+    let start = std::time::Instant::now();
+    let scans = loop {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for the first scan");
+        }
+        if let Ok(response) = get("/scans").await {
+            let scans: serde_json::Value = response.json().await.unwrap();
+            if !scans.as_array().unwrap().is_empty() {
+                break scans;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    let torrents: serde_json::Value = get("/torrents").await.unwrap().json().await.unwrap();
+    let config: serde_json::Value = get("/config").await.unwrap().json().await.unwrap();
+    let metrics = get("/metrics").await.unwrap().text().await.unwrap();
+    let health = get("/health").await.unwrap();
+    let health_code = health.status();
+    let health: serde_json::Value = health.json().await.unwrap();
+    let live_code = get("/health/live").await.unwrap().status();
+    let not_found = get("/database").await.unwrap().status();
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    assert_eq!(scans[0]["instance"], DEFAULT_INSTANCE);
+    assert_eq!(scans[0]["success"], true);
+    assert_eq!(scans[0]["tracked"], 2);
+    assert_eq!(scans[0]["due"], 1);
+
+    let torrents = torrents.as_array().unwrap();
+    assert_eq!(torrents.len(), 2);
+    let debian = torrents
+        .iter()
+        .find(|torrent| torrent["name"] == "debian.iso")
+        .unwrap();
+    assert_eq!(debian["reason"], "lifetime_after_copied");
+    assert_eq!(debian["expiry_date"], now - 400);
+    assert_eq!(debian["protected"], false);
+
+    assert_eq!(config["password"], "<redacted>");
+    assert!(metrics.contains("fp_torrents_tracked{instance=\"default\"} 2\n"));
+    assert!(metrics.contains("fp_torrents_pending_expiry{instance=\"default\"} 1\n"));
+    assert!(metrics.contains("# TYPE fp_scans_total counter\n"));
+    assert_eq!(health_code, 200);
+    assert_eq!(health["ready"], true);
+    assert_eq!(health["instances"][0]["reachable"], true);
+    assert_eq!(live_code, 200);
+    assert_eq!(not_found, 404);
+}
+
+struct UnreachableClient {}
+
+#[async_trait::async_trait]
+impl TorrentClient for UnreachableClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        Err("Connection refused".to_string())
+    }
+
+    async fn delete_file(&mut self, _hashes: &[String], _delete_data: bool) -> Result<(), String> {
+        Err("Connection refused".to_string())
+    }
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_with_webhook() {
+    let now = get_now_timestamp();
+    let mut server = mockito::Server::new_async().await;
+    let deleted_hook = server
+        .mock("POST", "/hook")
+        .match_header("authorization", "Bearer token")
+        .match_header("content-type", "application/json")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "content": "[box1] Removed 1 torrent(s) (delete_data): debian.iso"
+        })))
+        .with_status(204)
+        .create_async()
+        .await;
+    let failed_hook = server
+        .mock("POST", "/hook")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "content": "[box2] Scan failed: Connection refused"
+        })))
+        .with_status(204)
+        .create_async()
+        .await;
+
+    let client = FakeClient {
+        files: vec![File {
+            server_id: 1,
+            name: "debian.iso".to_string(),
+            added_date: now - 1000,
+            finish_date: Some(now - 500),
+            ..Default::default()
+        }],
+        deleted: Arc::new(std::sync::Mutex::new(vec![])),
+    };
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_instances(
+        vec![
+            Instance::new("box1", Box::new(client), Some(3600), Some(100)),
+            Instance::new("box2", Box::new(UnreachableClient {}), None, None),
+        ],
+        None,
+        Some(0),
+    );
+    monitor.set_webhooks(vec![Webhook {
+        url: format!("{}/hook", server.url()),
+        preset: WebhookPreset::Discord,
+        headers: vec![("Authorization".to_string(), "Bearer token".to_string())],
+        template: None,
+    }]);
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = std::time::Instant::now();
+    while !deleted_hook.matched_async().await || !failed_hook.matched_async().await {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for webhook calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    // the removed torrent and the unreachable client are only notified once
+    deleted_hook.assert_async().await;
+    failed_hook.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_monitor_reload() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = FakeClient {
+        files: vec![File {
+            id: 0,
+            server_id: 1,
+            added_date: now - 1000,
+            finish_date: Some(now - 500),
+            ..Default::default()
+        }],
+        deleted: deleted.clone(),
+    };
+    let database_path = std::env::temp_dir().join(format!("fp_reload_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+
+    let reload = |name: &str, lifetime_after_copied: u32| Reload {
+        scan_interval: Some(0),
+        instances: vec![InstanceSettings::new(
+            name,
+            Some(3600),
+            Some(lifetime_after_copied),
+        )],
+        rules: vec![],
+        webhooks: vec![],
+        mailer: None,
+        config: serde_json::json!({ "file_lifetime_after_copied": lifetime_after_copied }),
+    };
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    let mut monitor = Monitor::with_client(
+        Box::new(client),
+        Some(database_path.to_string_lossy().to_string()),
+        Some(0),
+        Some(3600),
+        Some(3600),
+    );
+    monitor.set_reloads(Some(receiver));
+    let status = monitor.status();
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    // the file is tracked but kept with the initial lifetime
+    let start = std::time::Instant::now();
+    while status.lock().await.torrents().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for the first scan");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(deleted.lock().unwrap().is_empty());
+
+    // instances can't be renamed, the settings are kept
+    sender.send(reload("box1", 100)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(deleted.lock().unwrap().is_empty());
+    assert_eq!(*status.lock().await.config(), serde_json::Value::Null);
+
+    // the new lifetime applies to the file tracked before the reload
+    sender.send(reload(DEFAULT_INSTANCE, 100)).await.unwrap();
+    let start = std::time::Instant::now();
+    while deleted.lock().unwrap().is_empty() {
+        if start.elapsed().as_secs() > TEST_TIMEOUT_SECS {
+            panic!("Timeout waiting for delete file calls");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        status.lock().await.config()["file_lifetime_after_copied"],
+        100
+    );
+
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    app_thread.await.unwrap();
+
+    // the database was kept, the file was tracked from its first scan
+    let mut database = Database::new(Some(database_path.to_string_lossy().to_string()));
+    database.connect().await.unwrap();
+    let history = database.list_history(&HistoryFilter::default()).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].reason, "lifetime_after_copied");
+    let _ = std::fs::remove_file(&database_path);
+}
+
+/// Client whose torrents take a while to fetch.
+struct SlowClient {
+    client: FakeClient,
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for SlowClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        self.client.fetch_files().await
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        self.client.delete_file(hashes, delete_data).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_stops_during_scan() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = SlowClient {
+        client: FakeClient {
+            files: vec![File {
+                id: 0,
+                server_id: 1,
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                ..Default::default()
+            }],
+            deleted: deleted.clone(),
+        },
+    };
+    let database_path = std::env::temp_dir().join(format!("fp_shutdown_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(
+        Box::new(client),
+        Some(database_path.to_string_lossy().to_string()),
+        Some(0),
+        Some(3600),
+        Some(100),
+    );
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    // stop while the torrents are fetched, the scan finishes without removing the due one
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    tokio::time::timeout(Duration::from_secs(TEST_TIMEOUT_SECS), app_thread)
+        .await
+        .expect("Timeout waiting for the monitor to stop")
+        .unwrap();
+    assert!(deleted.lock().unwrap().is_empty());
+
+    // the scan was saved before the database was closed
+    let mut database = Database::new(Some(database_path.to_string_lossy().to_string()));
+    database.connect().await.unwrap();
+    assert_eq!(database.list_of_file_ids(DEFAULT_INSTANCE).await.len(), 1);
+    let _ = std::fs::remove_file(&database_path);
+}
+
+/// Client whose removals are slow, e.g. rTorrent deleting a large directory.
+struct SlowDeleteClient {
+    client: FakeClient,
+    deleting: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl TorrentClient for SlowDeleteClient {
+    async fn fetch_files(&mut self) -> Result<Vec<File>, String> {
+        self.client.fetch_files().await
+    }
+
+    async fn delete_file(&mut self, hashes: &[String], delete_data: bool) -> Result<(), String> {
+        self.deleting.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        self.client.delete_file(hashes, delete_data).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_aborted_during_removal() {
+    let now = get_now_timestamp();
+    let deleted = Arc::new(std::sync::Mutex::new(vec![]));
+    let deleting = Arc::new(AtomicBool::new(false));
+    let client = SlowDeleteClient {
+        client: FakeClient {
+            files: vec![File {
+                id: 0,
+                server_id: 1,
+                added_date: now - 1000,
+                finish_date: Some(now - 500),
+                ..Default::default()
+            }],
+            deleted: deleted.clone(),
+        },
+        deleting: deleting.clone(),
+    };
+    let database_path = std::env::temp_dir().join(format!("fp_abort_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&database_path);
+
+    let stop_signal: Arc<Mutex<AtomicBool>> = Arc::new(Mutex::new(AtomicBool::new(false)));
+    let stop_signal_clone = stop_signal.clone();
+    let mut monitor = Monitor::with_client(
+        Box::new(client),
+        Some(database_path.to_string_lossy().to_string()),
+        Some(0),
+        Some(3600),
+        Some(100),
+    );
+    let removals = monitor.removals();
+    let app_thread = tokio::spawn(async move {
+        monitor.run(Some(stop_signal_clone)).await;
+    });
+
+    let start = get_now_timestamp();
+    while !deleting.load(Ordering::SeqCst) {
+        assert!(
+            get_now_timestamp() - start <= TEST_TIMEOUT_SECS as i64,
+            "Timeout waiting for the removal"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // stopped and aborted like the service does after its shutdown timeout
+    stop_signal.lock().await.store(true, Ordering::SeqCst);
+    let _removals = removals.write().await;
+    app_thread.abort();
+    let _ = app_thread.await;
+
+    // the removal in progress was recorded before the monitor was aborted
+    assert_eq!(*deleted.lock().unwrap(), vec![1]);
+    let database = {
+        let mut database = Database::new(Some(database_path.to_string_lossy().to_string()));
+        database.connect().await.unwrap();
+        database
+    };
+    let history = database.list_history(&HistoryFilter::default()).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].result, "success");
+    let _ = std::fs::remove_file(&database_path);
+}
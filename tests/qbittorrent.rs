@@ -0,0 +1,111 @@
+use fp::logic::api::TorrentClient;
+use fp::logic::api::qbittorrent::QBittorrentApi;
+use mockito::{Matcher, ServerGuard};
+
+const SID: &str = "SID=Z2hbEf9Gm0b1G0Zf6bY1qBT";
+
+fn setup_login_mock(server: &mut ServerGuard, username: &str, password: &str) -> mockito::Mock {
+    server
+        .mock("POST", "/api/v2/auth/login")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("username".to_string(), username.to_string()),
+            Matcher::UrlEncoded("password".to_string(), password.to_string()),
+        ]))
+        .with_status(200)
+        .with_header("set-cookie", format!("{}; HttpOnly; path=/", SID).as_str())
+        .with_body("Ok.")
+        .create()
+}
+
+#[tokio::test]
+async fn test_qbittorrent_list_files() {
+    let mut server = mockito::Server::new_async().await;
+    let username = "test_user";
+    let password = "test_password";
+
+    let login_mock = setup_login_mock(&mut server, username, password);
+    server
+        .mock("GET", "/api/v2/torrents/info")
+        .match_header("cookie", SID)
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body("[ {\"hash\": \"aaa\", \"added_on\": 1763580763, \"completion_on\": -1, \"progress\": 0.5}, {\"hash\": \"bbb\", \"added_on\": 1763580000, \"completion_on\": 1763580500, \"progress\": 1, \"name\": \"debian.iso\", \"total_size\": 4000, \"save_path\": \"/downloads\", \"category\": \"linux\", \"tags\": \"iso, big\", \"tracker\": \"https://tracker.org:443/announce\"} ]")
+        .create();
+
+    let mut api =
+        QBittorrentApi::new(username.to_string(), password.to_string(), &server.url()).unwrap();
+
+    match api.fetch_files().await {
+        Ok(files) => {
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].added_date, 1763580763);
+            assert_eq!(files[0].finish_date, None);
+            assert_eq!(files[1].added_date, 1763580000);
+            assert_eq!(files[1].finish_date, Some(1763580500));
+            assert_ne!(files[0].server_id, files[1].server_id);
+            assert_eq!(files[1].hash, "bbb");
+            assert_eq!(files[1].name, "debian.iso");
+            assert_eq!(files[1].size, 4000);
+            assert_eq!(files[1].download_dir, "/downloads");
+            assert_eq!(files[1].labels, vec!["linux", "iso", "big"]);
+            assert_eq!(files[1].trackers, vec!["tracker.org"]);
+        }
+        Err(e) => panic!("API fetch_files failed: {}", e),
+    }
+
+    // server ids are stable between scans
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    assert_eq!(files[0].server_id, 1);
+    assert_eq!(files[1].server_id, 2);
+    login_mock.assert();
+}
+
+#[tokio::test]
+async fn test_qbittorrent_login_failure() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/api/v2/auth/login")
+        .with_status(200)
+        .with_body("Fails.")
+        .create();
+
+    let mut api =
+        QBittorrentApi::new("user".to_string(), "wrong".to_string(), &server.url()).unwrap();
+
+    assert!(api.fetch_files().await.is_err());
+}
+
+#[tokio::test]
+async fn test_qbittorrent_delete_file() {
+    let mut server = mockito::Server::new_async().await;
+    let username = "test_user";
+    let password = "test_password";
+
+    setup_login_mock(&mut server, username, password);
+    server
+        .mock("GET", "/api/v2/torrents/info")
+        .match_header("cookie", SID)
+        .with_status(200)
+        .with_body("[ {\"hash\": \"aaa\", \"added_on\": 1763580763, \"completion_on\": 1763580800, \"progress\": 1} ]")
+        .create();
+    let delete_mock = server
+        .mock("POST", "/api/v2/torrents/delete")
+        .match_header("cookie", SID)
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("hashes".to_string(), "aaa".to_string()),
+            Matcher::UrlEncoded("deleteFiles".to_string(), "true".to_string()),
+        ]))
+        .with_status(200)
+        .create();
+
+    let mut api =
+        QBittorrentApi::new(username.to_string(), password.to_string(), &server.url()).unwrap();
+
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    match api.delete_file(&[files[0].hash.clone()], true).await {
+        Ok(_) => {}
+        Err(e) => panic!("API delete_file failed: {}", e),
+    }
+    delete_mock.assert();
+}
@@ -0,0 +1,170 @@
+use fp::logic::api::TorrentClient;
+use fp::logic::api::rtorrent::RTorrentApi;
+use mockito::Matcher;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIST_RESPONSE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<methodResponse><params><param><value><array><data>
+<value><array><data>
+<value><string>AAA</string></value>
+<value><i8>1763580763</i8></value>
+<value><i8>1763580900</i8></value>
+<value><i8>0</i8></value>
+<value><i8>0</i8></value>
+</data></array></value>
+<value><array><data>
+<value><string>BBB</string></value>
+<value><i8>0</i8></value>
+<value><i8>1763580000</i8></value>
+<value><i8>1763580500</i8></value>
+<value><i8>1</i8></value>
+</data></array></value>
+</data></array></value></param></params></methodResponse>";
+
+const EMPTY_RESPONSE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<methodResponse><params><param><value><i8>0</i8></value></param></params></methodResponse>";
+
+#[tokio::test]
+async fn test_rtorrent_list_files_over_http() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/RPC2")
+        .match_body(Matcher::Regex(
+            "<methodName>d.multicall2</methodName>".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/xml")
+        .with_body(LIST_RESPONSE)
+        .create();
+
+    let mut api = RTorrentApi::new(
+        String::new(),
+        String::new(),
+        format!("{}/RPC2", server.url()).as_str(),
+    )
+    .unwrap();
+
+    match api.fetch_files().await {
+        Ok(files) => {
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].server_id, 1);
+            assert_eq!(files[0].added_date, 1763580763);
+            assert_eq!(files[0].finish_date, None);
+            assert_eq!(files[1].server_id, 2);
+            assert_eq!(files[1].added_date, 1763580000);
+            assert_eq!(files[1].finish_date, Some(1763580500));
+        }
+        Err(e) => panic!("API fetch_files failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_rtorrent_delete_file_over_http() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/RPC2")
+        .match_body(Matcher::Regex(
+            "<methodName>d.multicall2</methodName>".to_string(),
+        ))
+        .with_status(200)
+        .with_body(LIST_RESPONSE)
+        .create();
+    let path_mock = server
+        .mock("POST", "/RPC2")
+        .match_body(Matcher::Regex(
+            "<methodName>d.data_path</methodName>.*BBB".to_string(),
+        ))
+        .with_status(200)
+        .with_body("<?xml version=\"1.0\"?><methodResponse><params><param><value><string>/downloads/BBB</string></value></param></params></methodResponse>")
+        .create();
+    let erase_mock = server
+        .mock("POST", "/RPC2")
+        .match_body(Matcher::Regex(
+            "<methodName>d.erase</methodName>.*BBB".to_string(),
+        ))
+        .with_status(200)
+        .with_body(EMPTY_RESPONSE)
+        .create();
+    let remove_mock = server
+        .mock("POST", "/RPC2")
+        .match_body(Matcher::Regex(
+            "<methodName>execute.throw</methodName>.*<string>rm</string>.*<string>/downloads/BBB</string>"
+                .to_string(),
+        ))
+        .with_status(200)
+        .with_body(EMPTY_RESPONSE)
+        .create();
+
+    let mut api = RTorrentApi::new(
+        String::new(),
+        String::new(),
+        format!("{}/RPC2", server.url()).as_str(),
+    )
+    .unwrap();
+
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    match api.delete_file(&[files[1].hash.clone()], true).await {
+        Ok(_) => {}
+        Err(e) => panic!("API delete_file failed: {}", e),
+    }
+    path_mock.assert();
+    erase_mock.assert();
+    remove_mock.assert();
+}
+
+#[tokio::test]
+async fn test_rtorrent_list_files_over_scgi() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let scgi_server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // read the netstring with the headers and then the body
+        let mut request = vec![];
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            if read == 0 || text.ends_with("</methodCall>") {
+                break;
+            }
+        }
+        let request = String::from_utf8(request).unwrap();
+
+        stream
+            .write_all(
+                format!(
+                    "Status: 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+                    LIST_RESPONSE.len(),
+                    LIST_RESPONSE
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+        request
+    });
+
+    let mut api = RTorrentApi::new(
+        String::new(),
+        String::new(),
+        format!("scgi://{}", address).as_str(),
+    )
+    .unwrap();
+
+    let files = api.fetch_files().await.expect("API fetch_files failed");
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[1].finish_date, Some(1763580500));
+
+    let request = scgi_server.await.unwrap();
+    let (length, rest) = request.split_once(':').expect("Invalid netstring");
+    let headers = &rest[..length.parse::<usize>().unwrap()];
+    assert!(headers.starts_with("CONTENT_LENGTH\0"));
+    assert!(headers.contains("SCGI\x001\0"));
+    assert!(request.contains("<methodName>d.multicall2</methodName>"));
+}